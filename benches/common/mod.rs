@@ -0,0 +1,77 @@
+#![allow(dead_code)]
+
+use nes6502::{Cpu, Mapper};
+
+#[path = "../../tests/common/mod.rs"]
+mod fixtures;
+
+pub use fixtures::InterruptsContainer;
+
+/// 64KB of ram. This lives on the heap, since the optimizer chokes on a 64KB array
+/// whose contents it can see.
+pub struct Memory(pub Box<[u8]>);
+
+impl Memory {
+    pub fn new() -> Self {
+        Self(vec![0; 0x10000].into_boxed_slice())
+    }
+
+    /// Creates memory with `program` loaded at $8000, which is also the reset vector.
+    pub fn with_program(program: &[u8]) -> Self {
+        let mut memory = Self::new();
+        memory.0[0x8000..0x8000 + program.len()].copy_from_slice(program);
+        memory.0[0xFFFD] = 0x80;
+        memory
+    }
+}
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0[address as usize] = byte
+    }
+}
+
+/// [`Memory`] that also provides its buffer as a snapshot region.
+pub struct RegionMemory(pub Memory);
+
+impl Mapper for RegionMemory {
+    fn read(&self, address: u16) -> u8 {
+        self.0.read(address)
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0.write(address, byte)
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0, &self.0 .0)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0, &mut self.0 .0)])
+    }
+}
+
+/// A mapper behind a trait object, to measure the cost of dynamic dispatch.
+pub struct DynMapper(pub Box<dyn Mapper>);
+
+impl Mapper for DynMapper {
+    fn read(&self, address: u16) -> u8 {
+        self.0.read(address)
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0.write(address, byte)
+    }
+}
+
+/// Creates an initialized cpu running from $8000.
+pub fn new_cpu<M: Mapper>(memory_mapper: M) -> Cpu<M, InterruptsContainer> {
+    let mut cpu = Cpu::new(memory_mapper, InterruptsContainer::default());
+    cpu.initialize();
+    cpu
+}
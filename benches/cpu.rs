@@ -0,0 +1,142 @@
+mod common;
+
+use common::{DynMapper, InterruptsContainer, Memory, RegionMemory};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use nes6502::{Cpu, Mapper};
+
+const INSTRUCTIONS: u64 = 10_000;
+
+/// ```text
+/// loop:  LDX #$00
+/// inner: DEX
+///        BNE inner
+///        JMP loop
+/// ```
+const COUNTDOWN: [u8; 8] = [0xA2, 0x00, 0xCA, 0xD0, 0xFD, 0x4C, 0x00, 0x80];
+
+/// Copies a page from $03F0 to $0600, crossing a page boundary on every read.
+///
+/// ```text
+/// start: LDY #$00
+/// loop:  LDA ($10),Y
+///        STA ($12),Y
+///        INY
+///        BNE loop
+///        JMP start
+/// ```
+const COPY: [u8; 12] = [
+    0xA0, 0x00, 0xB1, 0x10, 0x91, 0x12, 0xC8, 0xD0, 0xF9, 0x4C, 0x00, 0x80,
+];
+
+/// Spins while an interrupt handler at $9000 counts interrupts.
+///
+/// ```text
+///       CLI
+/// loop: NOP
+///       JMP loop
+///
+/// irq:  INC $00
+///       RTI
+/// ```
+const SPIN: [u8; 5] = [0x58, 0xEA, 0x4C, 0x01, 0x80];
+const IRQ_HANDLER: [u8; 3] = [0xE6, 0x00, 0x40];
+
+/// Raises an interrupt every this many instructions.
+const INTERRUPT_PERIOD: u64 = 8;
+
+fn run<M: Mapper>(cpu: &mut Cpu<M, InterruptsContainer>) {
+    for _ in 0..INSTRUCTIONS {
+        black_box(cpu.cycle());
+    }
+}
+
+fn copy_memory() -> Memory {
+    let mut memory = Memory::with_program(&COPY);
+    memory.0[0x10..0x14].copy_from_slice(&[0xF0, 0x03, 0x00, 0x06]);
+    for (i, byte) in memory.0[0x03F0..0x04F0].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    memory
+}
+
+fn loops(c: &mut Criterion) {
+    let mut group = c.benchmark_group("loops");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    group.bench_function("countdown", |b| {
+        let mut cpu = common::new_cpu(Memory::with_program(&COUNTDOWN));
+        b.iter(|| run(&mut cpu))
+    });
+
+    group.bench_function("countdown_dyn_mapper", |b| {
+        let memory = DynMapper(Box::new(Memory::with_program(&COUNTDOWN)));
+        let mut cpu = common::new_cpu(memory);
+        b.iter(|| run(&mut cpu))
+    });
+
+    group.bench_function("indirect_copy", |b| {
+        let mut cpu = common::new_cpu(copy_memory());
+        b.iter(|| run(&mut cpu))
+    });
+
+    group.bench_function("indirect_copy_dyn_mapper", |b| {
+        let mut cpu = common::new_cpu(DynMapper(Box::new(copy_memory())));
+        b.iter(|| run(&mut cpu))
+    });
+
+    group.bench_function("interrupts", |b| {
+        let mut memory = Memory::with_program(&SPIN);
+        memory.0[0x9000..0x9003].copy_from_slice(&IRQ_HANDLER);
+        memory.0[0xFFFF] = 0x90;
+
+        let mut cpu = common::new_cpu(memory);
+        b.iter(|| {
+            for i in 0..INSTRUCTIONS {
+                if i % INTERRUPT_PERIOD == 0 {
+                    cpu.interrupts.interrupt = true;
+                }
+                black_box(cpu.cycle());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn state(c: &mut Criterion) {
+    let mut group = c.benchmark_group("state");
+
+    let cpu = common::new_cpu(copy_memory());
+    group.bench_function("state_peek", |b| b.iter(|| black_box(cpu.state())));
+
+    let cpu = common::new_cpu(RegionMemory(copy_memory()));
+    group.bench_function("state_regions", |b| b.iter(|| black_box(cpu.state())));
+
+    let state = cpu.state();
+    group.bench_function("from_state_write", |b| {
+        b.iter_batched(
+            || state.clone(),
+            |state| {
+                let memory = Memory::new();
+                black_box(Cpu::from_state(state, memory, InterruptsContainer::default()))
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("from_state_regions", |b| {
+        b.iter_batched(
+            || state.clone(),
+            |state| {
+                let memory = RegionMemory(Memory::new());
+                black_box(Cpu::from_state(state, memory, InterruptsContainer::default()))
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, loops, state);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{InterruptsContainer, Memory};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use nes6502::{Cpu, FullOpcode};
+
+const INSTRUCTIONS: u64 = 10_000;
+
+/// A loop touching the common addressing modes:
+///
+/// ```text
+/// loop: LDA $10      ; zeropage
+///       ADC #$01     ; immediate
+///       STA $0200,X  ; absolute,x
+///       LDA ($20),Y  ; (indirect),y
+///       INX
+///       ASL A
+///       JMP loop
+/// ```
+fn new_cpu() -> Cpu<Memory, InterruptsContainer> {
+    common::new_cpu(Memory::with_program(&[
+        0xA5, 0x10, 0x69, 0x01, 0x9D, 0x00, 0x02, 0xB1, 0x20, 0xE8, 0x0A, 0x4C, 0x00, 0x80,
+    ]))
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    group.bench_function("instructions", |b| {
+        let mut cpu = new_cpu();
+        b.iter(|| {
+            for _ in 0..INSTRUCTIONS {
+                black_box(cpu.cycle());
+            }
+        })
+    });
+
+    group.finish();
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(256));
+    group.bench_function("try_new", |b| {
+        b.iter(|| {
+            for byte in 0..=255 {
+                black_box(FullOpcode::try_new(black_box(byte)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
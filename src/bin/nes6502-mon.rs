@@ -0,0 +1,605 @@
+//! An interactive monitor for loading and stepping through raw 6502 programs.
+//!
+//! Usage: `nes6502-mon <file> [load address] [start address]`
+//!
+//! The file is treated as Intel HEX if it ends in `.hex` or `.ihx`, and as a raw
+//! binary otherwise. Raw binaries are loaded at the load address (defaulting to $0000),
+//! while Intel HEX files carry their own addresses.
+
+use nes6502::{Cpu, Interrupts, Mapper, Opcode};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// The most instructions `g` and `n` run before giving the prompt back, so that a program
+/// which never stops can be interrupted.
+const RUN_LIMIT: u64 = 100_000_000;
+
+const HELP: &str = "\
+r                 show registers
+m addr [len]      hex dump memory
+d [addr] [count]  disassemble
+s [count]         step into
+n                 step over subroutine calls
+g [addr]          run until a breakpoint, trap, or illegal opcode, or for at most
+                  100 million instructions
+b [addr]          toggle a breakpoint, or list them
+w addr val...     write bytes to memory
+irq | nmi         raise an interrupt
+reset             reset the cpu
+bt                show the call stack
+q                 quit
+
+All numbers are hexadecimal and may be prefixed with $ or 0x.";
+
+struct Memory([u8; 0x10000]);
+
+impl Memory {
+    pub fn new() -> Self {
+        Self([0; 0x10000])
+    }
+}
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0[address as usize] = byte
+    }
+}
+
+#[derive(Default)]
+pub struct InterruptsContainer {
+    pub interrupt: bool,
+    pub non_maskable_interrupt: bool,
+}
+
+impl InterruptsContainer {
+    fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Interrupts for InterruptsContainer {
+    fn interrupt_state(&self) -> bool {
+        self.interrupt
+    }
+
+    fn set_interrupt_state(&mut self, new_state: bool) {
+        self.interrupt = new_state;
+    }
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        self.non_maskable_interrupt
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, new_state: bool) {
+        self.non_maskable_interrupt = new_state;
+    }
+}
+
+/// Why a run of instructions stopped.
+enum Stop {
+    Breakpoint,
+    /// The program counter did not change, which is how test programs usually signal
+    /// that they are done.
+    Trap,
+    IllegalOpcode(u8),
+    /// The requested amount of instructions were ran.
+    Finished,
+    /// [`RUN_LIMIT`] instructions were ran without stopping.
+    Limit,
+}
+
+struct Monitor {
+    cpu: Cpu<Memory, InterruptsContainer>,
+    breakpoints: BTreeSet<u16>,
+}
+
+impl Monitor {
+    fn new(cpu: Cpu<Memory, InterruptsContainer>) -> Self {
+        Self {
+            cpu,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Runs a single instruction, or services a pending interrupt.
+    fn step(&mut self) -> Stop {
+        let interrupt_pending = self.cpu.interrupts.non_maskable_interrupt
+            || (self.cpu.interrupts.interrupt
+                && !self.cpu.processor_status.interrupt_disable_flag());
+
+        let program_counter = self.cpu.program_counter;
+        if !interrupt_pending && self.cpu.disassemble(program_counter).is_none() {
+            return Stop::IllegalOpcode(self.cpu.peek(program_counter));
+        }
+
+        self.cpu.cycle();
+
+        match self.cpu.program_counter == program_counter {
+            true => Stop::Trap,
+            false => Stop::Finished,
+        }
+    }
+
+    /// Runs until `until` is reached, a breakpoint is hit, the program stops making
+    /// progress, or [`RUN_LIMIT`] instructions were ran.
+    fn run(&mut self, until: Option<u16>) -> Stop {
+        for _ in 0..RUN_LIMIT {
+            match self.step() {
+                Stop::Finished => {}
+                stop => return stop,
+            }
+
+            if Some(self.cpu.program_counter) == until {
+                return Stop::Finished;
+            }
+
+            if self.breakpoints.contains(&self.cpu.program_counter) {
+                return Stop::Breakpoint;
+            }
+        }
+
+        Stop::Limit
+    }
+
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(x) => x,
+            None => return Ok(true),
+        };
+        let arguments = words
+            .map(parse_number)
+            .collect::<Result<Vec<u16>, String>>()?;
+
+        match (command, arguments.as_slice()) {
+            ("r", []) => self.print_registers(),
+            ("m", [address]) => self.dump(*address, 0x40),
+            ("m", [address, length]) => self.dump(*address, *length),
+            ("d", []) => self.disassemble(self.cpu.program_counter, 10),
+            ("d", [address]) => self.disassemble(*address, 10),
+            ("d", [address, count]) => self.disassemble(*address, *count),
+            ("s", []) => {
+                let stop = self.step();
+                self.report(stop)
+            }
+            ("s", [count]) => {
+                let mut stop = Stop::Finished;
+                for _ in 0..*count {
+                    stop = self.step();
+                    if !matches!(stop, Stop::Finished) {
+                        break;
+                    }
+                }
+                self.report(stop)
+            }
+            ("n", []) => {
+                let program_counter = self.cpu.program_counter;
+                let stop = match self.cpu.disassemble(program_counter) {
+                    // run until the subroutine returns
+                    Some(instruction) if instruction.opcode == Opcode::JSR => {
+                        self.run(Some(program_counter.wrapping_add(instruction.size())))
+                    }
+                    _ => self.step(),
+                };
+                self.report(stop)
+            }
+            ("g", []) => {
+                let stop = self.run(None);
+                self.report(stop)
+            }
+            ("g", [address]) => {
+                self.cpu.program_counter = *address;
+                let stop = self.run(None);
+                self.report(stop)
+            }
+            ("b", []) => {
+                for breakpoint in &self.breakpoints {
+                    println!("${:04X}", breakpoint);
+                }
+            }
+            ("b", [address]) => match self.breakpoints.remove(address) {
+                true => println!("Removed breakpoint at ${:04X}", address),
+                false => {
+                    self.breakpoints.insert(*address);
+                    println!("Added breakpoint at ${:04X}", address);
+                }
+            },
+            ("w", [address, values @ ..]) if !values.is_empty() => {
+                for (offset, value) in values.iter().enumerate() {
+                    let value =
+                        u8::try_from(*value).map_err(|_| format!("${:X} is not a byte", value))?;
+                    self.cpu.write(address.wrapping_add(offset as u16), value);
+                }
+            }
+            ("irq", []) => self.cpu.interrupts.set_interrupt_state(true),
+            ("nmi", []) => self.cpu.interrupts.set_non_maskable_interrupt_state(true),
+            ("reset", []) => {
+                self.cpu.reset();
+                self.print_registers();
+            }
+            ("h" | "?", []) => println!("{}", HELP),
+            ("bt", []) => self.print_backtrace(),
+            ("q", []) => return Ok(false),
+            _ => {
+                return Err(format!(
+                    "Invalid command `{}`. Type `h` for help.",
+                    line.trim()
+                ))
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Breakpoint => println!("Breakpoint hit"),
+            Stop::Trap => println!("Trapped at ${:04X}", self.cpu.program_counter),
+            Stop::IllegalOpcode(opcode) => println!(
+                "Illegal opcode ${:02X} at ${:04X}",
+                opcode, self.cpu.program_counter
+            ),
+            Stop::Limit => println!("Stopped after {} instructions", RUN_LIMIT),
+            Stop::Finished => {}
+        }
+
+        self.disassemble(self.cpu.program_counter, 1);
+    }
+
+    fn print_registers(&self) {
+        let status = &self.cpu.processor_status;
+        let flags = [
+            (status.negative_flag(), 'N'),
+            (status.overflow_flag(), 'V'),
+            (status.bit_5_flag(), '-'),
+            (status.break_flag(), 'B'),
+            (status.decimal_flag(), 'D'),
+            (status.interrupt_disable_flag(), 'I'),
+            (status.zero_flag(), 'Z'),
+            (status.carry_flag(), 'C'),
+        ]
+        .iter()
+        .map(|(set, name)| match set {
+            true => *name,
+            false => '.',
+        })
+        .collect::<String>();
+
+        println!(
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} [{}] CYC:{}",
+            self.cpu.program_counter,
+            self.cpu.accumulator,
+            self.cpu.x,
+            self.cpu.y,
+            self.cpu.stack_pointer,
+            status.0,
+            flags,
+            self.cpu.cycles
+        );
+    }
+
+    fn print_backtrace(&self) {
+        for (depth, frame) in self.cpu.backtrace().iter().rev().enumerate() {
+            println!(
+                "#{:<3} ${:04X} {:?} from ${:04X} (SP:{:02X})",
+                depth, frame.target, frame.kind, frame.caller, frame.stack_pointer
+            );
+        }
+
+        if let Some(mismatch) = self.cpu.call_stack.as_ref().and_then(|x| x.last_mismatch()) {
+            println!("Last stack mismatch: {:?}", mismatch);
+        }
+    }
+
+    fn dump(&self, address: u16, length: u16) {
+        for row_start in (0..length).step_by(16) {
+            let row_address = address.wrapping_add(row_start);
+            let bytes = (row_start..length.min(row_start.saturating_add(16)))
+                .map(|offset| self.cpu.peek(address.wrapping_add(offset)))
+                .collect::<Vec<u8>>();
+
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|byte| match byte.is_ascii_graphic() || *byte == b' ' {
+                    true => *byte as char,
+                    false => '.',
+                })
+                .collect::<String>();
+
+            println!("{:04X}  {:<47}  {}", row_address, hex, ascii);
+        }
+    }
+
+    fn disassemble(&self, mut address: u16, count: u16) {
+        for _ in 0..count {
+            let marker = match self.breakpoints.contains(&address) {
+                true => '*',
+                false => ' ',
+            };
+
+            match self.cpu.disassemble(address) {
+                Some(instruction) => {
+                    let bytes = (0..instruction.size())
+                        .map(|offset| {
+                            format!("{:02X}", self.cpu.peek(address.wrapping_add(offset)))
+                        })
+                        .collect::<Vec<String>>()
+                        .join(" ");
+
+                    let target = match instruction.branch_target(address) {
+                        Some(target) => format!(" ; -> ${:04X}", target),
+                        None => String::new(),
+                    };
+
+                    println!(
+                        "{}{:04X}  {:<8}  {}{}",
+                        marker, address, bytes, instruction, target
+                    );
+                    address = address.wrapping_add(instruction.size());
+                }
+                None => {
+                    println!(
+                        "{}{:04X}  {:02X}        ???",
+                        marker,
+                        address,
+                        self.cpu.peek(address)
+                    );
+                    address = address.wrapping_add(1);
+                }
+            }
+        }
+    }
+}
+
+/// Parses a hexadecimal number, which may be prefixed with $ or 0x.
+fn parse_number(word: &str) -> Result<u16, String> {
+    let digits = word
+        .strip_prefix('$')
+        .or_else(|| word.strip_prefix("0x"))
+        .unwrap_or(word);
+
+    u16::from_str_radix(digits, 16).map_err(|_| format!("`{}` is not a valid number", word))
+}
+
+/// Loads an Intel HEX file into memory. Returns the start address of the file, or the
+/// address of the first data record if it does not have one.
+fn load_intel_hex(memory: &mut Memory, text: &str) -> Result<Option<u16>, String> {
+    let mut base_address = 0u32;
+    let mut start_address = None;
+    let mut first_data_address = None;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = || format!("Invalid Intel HEX record on line {}", line_number + 1);
+
+        let record = line.strip_prefix(':').ok_or_else(invalid)?;
+        if !record.len().is_multiple_of(2) || record.len() < 10 {
+            return Err(invalid());
+        }
+
+        let bytes = (0..record.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&record[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        let length = bytes[0] as usize;
+        if bytes.len() != length + 5 {
+            return Err(invalid());
+        }
+
+        let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if checksum != 0 {
+            return Err(format!("Bad checksum on line {}", line_number + 1));
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..4 + length];
+
+        match bytes[3] {
+            // data
+            0x00 => {
+                first_data_address = first_data_address.or(Some((base_address + offset) as u16));
+                for (i, byte) in data.iter().enumerate() {
+                    let address = base_address + offset + i as u32;
+                    memory.0[(address & 0xFFFF) as usize] = *byte;
+                }
+            }
+            // end of file
+            0x01 => break,
+            // extended segment address
+            0x02 if length == 2 => {
+                base_address = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4
+            }
+            // extended linear address
+            0x04 if length == 2 => {
+                base_address = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16
+            }
+            // start segment address (CS:IP), we only care about IP
+            0x03 if length == 4 => start_address = Some(u16::from_be_bytes([data[2], data[3]])),
+            // start linear address
+            0x05 if length == 4 => start_address = Some(u16::from_be_bytes([data[2], data[3]])),
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(start_address.or(first_data_address))
+}
+
+fn main() {
+    let arguments = std::env::args().skip(1).collect::<Vec<String>>();
+    if arguments.is_empty() || arguments.len() > 3 {
+        eprintln!("Usage: nes6502-mon <file> [load address] [start address]");
+        std::process::exit(1);
+    }
+
+    let numbers = match arguments[1..]
+        .iter()
+        .map(|x| parse_number(x))
+        .collect::<Result<Vec<u16>, String>>()
+    {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let load_address = numbers.first().copied().unwrap_or(0);
+
+    let path = Path::new(&arguments[0]);
+    let bytes = match std::fs::read(path) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut memory = Memory::new();
+    let is_intel_hex = matches!(
+        path.extension().and_then(|x| x.to_str()),
+        Some("hex" | "ihx")
+    );
+
+    let file_start_address = match is_intel_hex {
+        true => match load_intel_hex(&mut memory, &String::from_utf8_lossy(&bytes)) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        false => {
+            for (offset, byte) in bytes.iter().enumerate() {
+                memory.0[(load_address as usize + offset) & 0xFFFF] = *byte;
+            }
+            println!("Loaded {} bytes at ${:04X}", bytes.len(), load_address);
+            None
+        }
+    };
+
+    let mut cpu = Cpu::new(memory, InterruptsContainer::new());
+    cpu.enable_call_stack();
+    cpu.initialize();
+    cpu.program_counter = numbers
+        .get(1)
+        .copied()
+        .or(file_start_address)
+        .unwrap_or(load_address);
+
+    let mut monitor = Monitor::new(cpu);
+    monitor.print_registers();
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => panic!("{}", e),
+        }
+
+        match monitor.execute(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn monitor() -> Monitor {
+        let mut cpu = Cpu::new(Memory::new(), InterruptsContainer::new());
+        cpu.initialize();
+        Monitor::new(cpu)
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("$C000"), Ok(0xC000));
+        assert_eq!(parse_number("0xc000"), Ok(0xC000));
+        assert_eq!(parse_number("c000"), Ok(0xC000));
+        assert!(parse_number("10000").is_err());
+        assert!(parse_number("$").is_err());
+    }
+
+    #[test]
+    fn test_load_intel_hex() {
+        // LDA #$42 at $8000, then the start address
+        let text = ":03800000A9420092\n:040000050000800077\n:00000001FF\n";
+        let mut memory = Memory::new();
+        assert_eq!(load_intel_hex(&mut memory, text), Ok(Some(0x8000)));
+        assert_eq!(memory.0[0x8000..0x8003], [0xA9, 0x42, 0x00]);
+
+        // without a start address, the first data record is used
+        let text = ":01000000EA15\n:03800000A9420092\n";
+        assert_eq!(load_intel_hex(&mut Memory::new(), text), Ok(Some(0x0000)));
+
+        // the extended linear address is added to the offsets, wrapping around
+        let text = ":020000040001F9\n:01000000EA15\n";
+        let mut memory = Memory::new();
+        memory.0[0] = 0xFF;
+        assert_eq!(load_intel_hex(&mut memory, text), Ok(Some(0x0000)));
+        assert_eq!(memory.0[0], 0xEA);
+
+        assert_eq!(
+            load_intel_hex(&mut Memory::new(), ":01000000EA16"),
+            Err("Bad checksum on line 1".to_string())
+        );
+        assert_eq!(
+            load_intel_hex(&mut Memory::new(), "\n01000000EA15"),
+            Err("Invalid Intel HEX record on line 2".to_string())
+        );
+        assert!(load_intel_hex(&mut Memory::new(), ":02000000EA13").is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut monitor = monitor();
+        assert_eq!(monitor.execute(""), Ok(true));
+
+        assert_eq!(monitor.execute("w $10 1 2"), Ok(true));
+        assert_eq!(monitor.cpu.memory_mapper.0[0x10..0x12], [1, 2]);
+        assert!(monitor.execute("w 10 100").is_err());
+        assert!(monitor.execute("w 10").is_err());
+
+        monitor.execute("b 8000").unwrap();
+        assert!(monitor.breakpoints.contains(&0x8000));
+        monitor.execute("b 8000").unwrap();
+        assert!(monitor.breakpoints.is_empty());
+
+        // dumps up to the end of the address space
+        assert_eq!(monitor.execute("m 0 ffff"), Ok(true));
+        assert_eq!(monitor.execute("m fff0 ffff"), Ok(true));
+
+        // NOP, then a JMP to itself
+        monitor.execute("w 8000 ea 4c 01 80").unwrap();
+        monitor.execute("g 8000").unwrap();
+        assert_eq!(monitor.cpu.program_counter, 0x8001);
+
+        assert!(monitor.execute("bogus").is_err());
+        assert!(monitor.execute("r 1").is_err());
+        assert!(monitor.execute("m xyz").is_err());
+        assert_eq!(monitor.execute("q"), Ok(false));
+    }
+}
@@ -0,0 +1,182 @@
+//! Runs blargg's test ROMs (such as `instr_test-v5`, `instr_misc` and `cpu_timing_test6`)
+//! headless. These report through PRG-RAM: $6000 holds the status, $6001-$6003 the
+//! signature DE B0 61 once the status is valid, and $6004 a NUL-terminated message.
+
+use crate::memory::InterruptsContainer;
+use nes6502::bus::{Cartridge, NesBus, PpuPort, Unconnected};
+use nes6502::mappers;
+use nes6502::rom::Rom;
+use nes6502::Cpu;
+use std::cell::Cell;
+use std::path::Path;
+
+const STATUS_ADDRESS: u16 = 0x6000;
+const SIGNATURE_ADDRESS: u16 = 0x6001;
+const MESSAGE_ADDRESS: u16 = 0x6004;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+/// The test is still running.
+const STATUS_RUNNING: u8 = 0x80;
+/// The test wants the cpu to be reset after at least 100ms.
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+/// The NTSC cpu clock rate, used to turn cycles into time.
+const CYCLES_PER_SECOND: u64 = 1_789_773;
+const RESET_DELAY_CYCLES: u64 = CYCLES_PER_SECOND / 8;
+/// How long a ROM may run before it is considered hung.
+pub const DEFAULT_TIMEOUT_CYCLES: u64 = CYCLES_PER_SECOND * 60;
+
+/// Just enough of the PPU for the ROMs to get past waiting for vblank: reads of $2002
+/// alternate the vblank flag, so waits for it in either direction end.
+#[derive(Default)]
+struct VblankToggle {
+    vblank: Cell<bool>,
+}
+
+impl PpuPort for VblankToggle {
+    fn read_register(&self, register: u8) -> u8 {
+        match register {
+            2 => {
+                let vblank = !self.vblank.get();
+                self.vblank.set(vblank);
+                (vblank as u8) << 7
+            }
+            _ => 0,
+        }
+    }
+
+    fn peek_register(&self, register: u8) -> u8 {
+        match register {
+            2 => (self.vblank.get() as u8) << 7,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, _register: u8, _byte: u8) {}
+}
+
+type Bus = NesBus<VblankToggle, Unconnected, Unconnected, Box<dyn Cartridge>>;
+
+/// How a ROM finished.
+pub enum Outcome {
+    /// The ROM reported a status, where 0 is a pass.
+    Finished { status: u8, message: String },
+    TimedOut { message: String },
+    IllegalOpcode { address: u16 },
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        matches!(self, Outcome::Finished { status: 0, .. })
+    }
+}
+
+/// Runs a ROM until it reports a result or runs for longer than `timeout_cycles`.
+pub fn run(path: &Path, timeout_cycles: u64) -> Result<Outcome, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let rom = Rom::from_ines(&bytes).map_err(|e| e.to_string())?;
+    let cartridge = mappers::from_rom(&rom).map_err(|e| e.to_string())?;
+    let bus = NesBus::new(VblankToggle::default(), Unconnected, Unconnected, cartridge);
+
+    let mut cpu = Cpu::new(bus, InterruptsContainer::new());
+    cpu.initialize();
+
+    let mut reset_at = None;
+
+    loop {
+        let address = cpu.program_counter;
+        let (_, success, _) = cpu.cycle_debug();
+        if !success {
+            return Ok(Outcome::IllegalOpcode { address });
+        }
+
+        if cpu.cycles >= timeout_cycles {
+            return Ok(Outcome::TimedOut {
+                message: read_message(&cpu),
+            });
+        }
+
+        let signature = [
+            cpu.peek(SIGNATURE_ADDRESS),
+            cpu.peek(SIGNATURE_ADDRESS + 1),
+            cpu.peek(SIGNATURE_ADDRESS + 2),
+        ];
+        if signature != SIGNATURE {
+            continue;
+        }
+
+        match cpu.peek(STATUS_ADDRESS) {
+            STATUS_RUNNING => {}
+            STATUS_NEEDS_RESET => match reset_at {
+                None => reset_at = Some(cpu.cycles + RESET_DELAY_CYCLES),
+                Some(cycles) if cpu.cycles >= cycles => {
+                    reset_at = None;
+                    cpu.reset();
+                }
+                Some(_) => {}
+            },
+            status => {
+                return Ok(Outcome::Finished {
+                    status,
+                    message: read_message(&cpu),
+                })
+            }
+        }
+    }
+}
+
+fn read_message(cpu: &Cpu<Bus, InterruptsContainer>) -> String {
+    let bytes = (MESSAGE_ADDRESS..0x8000)
+        .map(|address| cpu.peek(address))
+        .take_while(|&byte| byte != 0)
+        .collect::<Vec<u8>>();
+
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
+
+/// Runs every ROM and prints their results, returning true if any failed.
+pub fn run_all(paths: &[impl AsRef<Path>], timeout_cycles: u64) -> bool {
+    let mut failed = 0;
+
+    for path in paths {
+        let path = path.as_ref();
+        let outcome = match run(path, timeout_cycles) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("{}: could not be loaded: {}", path.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let result = match outcome.passed() {
+            true => "passed",
+            false => "failed",
+        };
+
+        match &outcome {
+            Outcome::Finished { status, message } => {
+                println!("{}: {} (status {})", path.display(), result, status);
+                if !message.is_empty() {
+                    println!("{}", message);
+                }
+            }
+            Outcome::TimedOut { message } => {
+                println!("{}: timed out", path.display());
+                if !message.is_empty() {
+                    println!("{}", message);
+                }
+            }
+            Outcome::IllegalOpcode { address } => {
+                println!("{}: illegal opcode at ${:04X}", path.display(), address);
+            }
+        }
+
+        if !outcome.passed() {
+            failed += 1;
+        }
+    }
+
+    println!("{} of {} ROMs passed", paths.len() - failed, paths.len());
+    failed > 0
+}
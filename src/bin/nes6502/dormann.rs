@@ -0,0 +1,132 @@
+//! Runs Klaus Dormann's 6502 functional test (https://github.com/Klaus2m5/6502_65C02_functional_tests),
+//! which is a 64KB memory image that loops on itself ("traps") when a test fails.
+
+use crate::memory::{InterruptsContainer, Memory};
+use nes6502::{Cpu, Variant};
+use std::path::Path;
+
+/// Where the test starts running.
+pub const DEFAULT_START_ADDRESS: u16 = 0x0400;
+/// The address of the success trap in the prebuilt `6502_functional_test.bin`.
+pub const DEFAULT_SUCCESS_ADDRESS: u16 = 0x3469;
+/// The address of the `test_case` variable, which holds the number of the running test.
+pub const DEFAULT_TEST_CASE_ADDRESS: u16 = 0x0200;
+
+const SED: u8 = 0xF8;
+
+pub struct Options {
+    pub start_address: u16,
+    pub success_address: u16,
+    pub test_case_address: u16,
+    pub variant: Variant,
+}
+
+/// How the test ended.
+pub enum Outcome {
+    /// The success address was reached.
+    Success,
+    /// A test using decimal mode failed on a variant without decimal mode, which is
+    /// expected of binaries assembled with `disable_decimal = 0` (the default).
+    DecimalTrap { address: u16, test_case: u8 },
+    Trap { address: u16, test_case: u8 },
+    IllegalOpcode { address: u16, test_case: u8 },
+}
+
+pub struct Report {
+    pub outcome: Outcome,
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+pub fn run(path: &Path, options: &Options) -> std::io::Result<Report> {
+    let bytes = std::fs::read(path)?;
+    let mut memory = Memory::new();
+    let length = bytes.len().min(memory.0.len());
+    memory.0[..length].copy_from_slice(&bytes[..length]);
+
+    let mut cpu = Cpu::new(memory, InterruptsContainer::new()).with_variant(options.variant);
+    cpu.initialize();
+    cpu.program_counter = options.start_address;
+
+    let mut instructions = 0;
+    let mut test_case = cpu.peek(options.test_case_address);
+    // whether the current test has used decimal mode
+    let mut decimal_used = false;
+
+    let outcome = loop {
+        let address = cpu.program_counter;
+        if address == options.success_address {
+            break Outcome::Success;
+        }
+
+        if cpu.peek(address) == SED && !options.variant.has_decimal_mode() {
+            decimal_used = true;
+        }
+
+        let (_, success, _) = cpu.cycle_debug();
+        if !success {
+            break Outcome::IllegalOpcode { address, test_case };
+        }
+        instructions += 1;
+
+        // traps are a jump or branch to themselves
+        if cpu.program_counter == address {
+            break match decimal_used {
+                true => Outcome::DecimalTrap { address, test_case },
+                false => Outcome::Trap { address, test_case },
+            };
+        }
+
+        let current_test_case = cpu.peek(options.test_case_address);
+        if current_test_case != test_case {
+            test_case = current_test_case;
+            decimal_used = false;
+        }
+    };
+
+    Ok(Report {
+        outcome,
+        instructions,
+        cycles: cpu.cycles,
+    })
+}
+
+/// Prints the report, returning true if the test failed. Decimal mode failures are not
+/// counted as failures.
+pub fn print_report(report: &Report) -> bool {
+    let failed = match report.outcome {
+        Outcome::Success => {
+            println!("Functional test passed");
+            false
+        }
+        Outcome::DecimalTrap { address, test_case } => {
+            println!(
+                "Functional test passed up to test ${:02X}, which trapped at ${:04X} while testing decimal mode",
+                test_case, address
+            );
+            println!("The cpu has no decimal mode, assemble the test with disable_decimal = 1 to skip it");
+            false
+        }
+        Outcome::Trap { address, test_case } => {
+            println!(
+                "Functional test ${:02X} failed, trapped at ${:04X}",
+                test_case, address
+            );
+            true
+        }
+        Outcome::IllegalOpcode { address, test_case } => {
+            println!(
+                "Functional test ${:02X} failed, illegal opcode at ${:04X}",
+                test_case, address
+            );
+            true
+        }
+    };
+
+    println!(
+        "Ran {} instructions in {} cycles",
+        report.instructions, report.cycles
+    );
+
+    failed
+}
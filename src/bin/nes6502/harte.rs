@@ -0,0 +1,151 @@
+//! Runs the single step tests from https://github.com/SingleStepTests/65x02, which
+//! have one JSON file per opcode.
+
+use crate::memory::{InterruptsContainer, Memory};
+use nes6502::{Cpu, CpuState, Variant};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Example {
+    name: String,
+    #[serde(rename = "initial")]
+    initial_state: CpuState,
+    #[serde(rename = "final")]
+    final_state: CpuState,
+    cycles: Vec<Vec<CyclePart>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CyclePart {
+    Integer(u64),
+    String(String),
+}
+
+/// The first failing test of an opcode.
+pub struct Failure {
+    pub name: String,
+    pub message: String,
+}
+
+/// The results of every test of a single opcode.
+pub struct OpcodeResult {
+    pub opcode: String,
+    pub passed: usize,
+    pub failed: usize,
+    /// Tests of opcodes the cpu considers illegal.
+    pub skipped: usize,
+    pub first_failure: Option<Failure>,
+}
+
+/// Returns the test files in `dir`, sorted by opcode and filtered to `opcodes` if it is
+/// not empty.
+pub fn find_test_files(dir: &Path, opcodes: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+
+        let included = opcodes.is_empty() || opcodes.iter().any(|x| *x == opcode_of(&path));
+        if included {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// The opcode a test file is for, which is its lowercase file name.
+fn opcode_of(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Runs every test in a file. Stops early once `stop` is set, and sets it on the first
+/// failure unless `keep_going` is true.
+pub fn run_file(path: &Path, variant: Variant, keep_going: bool, stop: &AtomicBool) -> OpcodeResult {
+    let mut result = OpcodeResult {
+        opcode: opcode_of(path),
+        passed: 0,
+        failed: 0,
+        skipped: 0,
+        first_failure: None,
+    };
+
+    let examples = match std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| sonic_rs::from_slice::<Vec<Example>>(&bytes).map_err(|e| e.to_string()))
+    {
+        Ok(x) => x,
+        Err(e) => {
+            result.failed += 1;
+            result.first_failure = Some(Failure {
+                name: path.display().to_string(),
+                message: format!("Could not read test file: {}\n", e),
+            });
+            return result;
+        }
+    };
+
+    for example in examples {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        match run_example(example, variant) {
+            Ok(true) => result.passed += 1,
+            Ok(false) => result.skipped += 1,
+            Err(failure) => {
+                result.failed += 1;
+                if result.first_failure.is_none() {
+                    result.first_failure = Some(failure);
+                }
+                if !keep_going {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Runs a single test, returning false if it was skipped.
+fn run_example(example: Example, variant: Variant) -> Result<bool, Failure> {
+    let memory = Memory::new();
+    let interrupts = InterruptsContainer::new();
+
+    let mut cpu = match Cpu::from_state(example.initial_state, memory, interrupts) {
+        Ok(cpu) => cpu.with_variant(variant),
+        Err(mismatch) => {
+            return Err(Failure {
+                name: example.name,
+                message: mismatch.to_string(),
+            })
+        }
+    };
+
+    let (_, success, instruction) = cpu.cycle_debug();
+
+    if !success {
+        // skip invalid instruction
+        return Ok(false);
+    }
+
+    let diff = cpu.state().diff(&example.final_state);
+    match diff.is_empty() {
+        true => Ok(true),
+        false => Err(Failure {
+            name: example.name,
+            message: format!("{}\n{}", instruction.unwrap(), diff),
+        }),
+    }
+}
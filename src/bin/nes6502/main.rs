@@ -0,0 +1,280 @@
+//! Runs the cpu against test suites.
+//!
+//! Usage: `nes6502 [--dir <path>] [--variant <name>] [--opcode <hex>]... [--keep-going] [--threads <n>]`
+//! or `nes6502 --dormann <file> [--variant <name>] [--success <addr>] [--start <addr>] [--test-case <addr>]`
+//! or `nes6502 --blargg <rom>... [--max-cycles <n>]`
+
+mod blargg;
+mod dormann;
+mod harte;
+mod memory;
+
+use harte::OpcodeResult;
+use nes6502::Variant;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The directory of the single step tests of each variant.
+fn default_test_directory(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Ricoh2A03 => "65x02/nes6502/v1",
+        Variant::Nmos6502 => "65x02/6502/v1",
+        Variant::Wdc65C02 => "65x02/wdc65c02/v1",
+    }
+}
+
+const USAGE: &str = "\
+Usage: nes6502 [options]
+
+Options:
+    --dir <path>       the directory of the tests (default: 65x02/nes6502/v1,
+                       65x02/6502/v1 for the 6502 variant or 65x02/wdc65c02/v1 for
+                       the 65c02 variant)
+    --variant <name>   the chip to emulate, 2a03, 6502 or 65c02 (default: 2a03)
+    --opcode <hex>     only run the tests of an opcode, may be repeated
+    --keep-going       run every test instead of stopping at the first failure
+    --threads <n>      the amount of test files ran at once (default: all cores)
+    -h, --help         show this message
+
+Klaus Dormann's functional test:
+    --dormann <file>     run the functional test from a 64KB memory image
+    --success <addr>     the address of the success trap (default: 3469)
+    --start <addr>       the address the test starts at (default: 0400)
+    --test-case <addr>   the address of the test_case variable (default: 0200)
+
+blargg's test ROMs:
+    --blargg <rom>...    run test ROMs that report through $6000
+    --max-cycles <n>     the cycles a ROM may run before timing out (default: 60 seconds)";
+
+struct Options {
+    dir: PathBuf,
+    variant: Variant,
+    opcodes: Vec<String>,
+    keep_going: bool,
+    threads: usize,
+    /// The memory image of the functional test, which is ran instead of the single step tests.
+    dormann: Option<PathBuf>,
+    dormann_options: dormann::Options,
+    /// ROMs reporting with blargg's protocol, which are ran instead of the single step tests.
+    blargg: Vec<PathBuf>,
+    max_cycles: u64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        dir: PathBuf::new(),
+        variant: Variant::default(),
+        opcodes: Vec::new(),
+        keep_going: false,
+        threads: std::thread::available_parallelism()
+            .map(|x| x.get())
+            .unwrap_or(1),
+        dormann: None,
+        dormann_options: dormann::Options {
+            start_address: dormann::DEFAULT_START_ADDRESS,
+            success_address: dormann::DEFAULT_SUCCESS_ADDRESS,
+            test_case_address: dormann::DEFAULT_TEST_CASE_ADDRESS,
+            variant: Variant::default(),
+        },
+        blargg: Vec::new(),
+        max_cycles: blargg::DEFAULT_TIMEOUT_CYCLES,
+    };
+
+    let mut dir = None;
+    let mut arguments = std::env::args().skip(1).peekable();
+    while let Some(argument) = arguments.next() {
+        let mut value = |name: &str| {
+            arguments
+                .next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+
+        match argument.as_str() {
+            "--dir" => dir = Some(PathBuf::from(value("--dir")?)),
+            "--variant" => {
+                options.variant = match value("--variant")?.to_lowercase().as_str() {
+                    "2a03" => Variant::Ricoh2A03,
+                    "6502" => Variant::Nmos6502,
+                    "65c02" => Variant::Wdc65C02,
+                    x => return Err(format!("Unknown variant: {}", x)),
+                }
+            }
+            "--opcode" => {
+                for opcode in value("--opcode")?.split(',') {
+                    let opcode = opcode.trim_start_matches("0x").to_lowercase();
+                    if u8::from_str_radix(&opcode, 16).is_err() {
+                        return Err(format!("Invalid opcode: {}", opcode));
+                    }
+                    options.opcodes.push(format!("{:0>2}", opcode));
+                }
+            }
+            "--keep-going" => options.keep_going = true,
+            "--threads" => {
+                options.threads = match value("--threads")?.parse() {
+                    Ok(x) if x > 0 => x,
+                    _ => return Err("--threads must be a positive number".to_string()),
+                }
+            }
+            "--dormann" => options.dormann = Some(PathBuf::from(value("--dormann")?)),
+            "--success" => {
+                options.dormann_options.success_address = parse_address(&value("--success")?)?
+            }
+            "--start" => {
+                options.dormann_options.start_address = parse_address(&value("--start")?)?
+            }
+            "--test-case" => {
+                options.dormann_options.test_case_address = parse_address(&value("--test-case")?)?
+            }
+            "--blargg" => {
+                options.blargg.push(PathBuf::from(value("--blargg")?));
+                // every following path is a ROM as well
+                while let Some(path) = arguments.next_if(|x| !x.starts_with('-')) {
+                    options.blargg.push(PathBuf::from(path));
+                }
+            }
+            "--max-cycles" => {
+                options.max_cycles = match value("--max-cycles")?.parse() {
+                    Ok(x) => x,
+                    _ => return Err("--max-cycles must be a number".to_string()),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => return Err(format!("Unknown argument: {}\n\n{}", argument, USAGE)),
+        }
+    }
+
+    options.dir = dir.unwrap_or_else(|| PathBuf::from(default_test_directory(options.variant)));
+    options.dormann_options.variant = options.variant;
+
+    Ok(options)
+}
+
+/// Parses a hexadecimal address, which may be prefixed with $ or 0x.
+fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid address: {}", text))
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let start = Instant::now();
+    let failed = match &options.dormann {
+        Some(path) => run_dormann(path, &options.dormann_options),
+        None if !options.blargg.is_empty() => blargg::run_all(&options.blargg, options.max_cycles),
+        None => run_single_step(&options),
+    };
+    println!("Finished in {:.2?}", start.elapsed());
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Runs Klaus Dormann's functional test, returning true if it failed.
+fn run_dormann(path: &Path, options: &dormann::Options) -> bool {
+    match dormann::run(path, options) {
+        Ok(report) => dormann::print_report(&report),
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the single step tests, returning true if any failed.
+fn run_single_step(options: &Options) -> bool {
+    let files = match harte::find_test_files(&options.dir, &options.opcodes) {
+        Ok(x) => x,
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                eprintln!("Required tests not found. Please clone the repository located at https://github.com/SingleStepTests/65x02 to this folder.");
+                std::process::exit(1);
+            }
+            _ => panic!("{}", e),
+        },
+    };
+
+    if files.is_empty() {
+        eprintln!("No test files matched in {}", options.dir.display());
+        std::process::exit(1);
+    }
+
+    let results = run_files(&files, options);
+    print_summary(&results)
+}
+
+/// Runs the test files on a pool of threads, returning the results sorted by opcode.
+fn run_files(files: &[PathBuf], options: &Options) -> Vec<OpcodeResult> {
+    let next_file = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let results = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.min(files.len()) {
+            scope.spawn(|| loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let path = match files.get(next_file.fetch_add(1, Ordering::Relaxed)) {
+                    Some(x) => x,
+                    None => break,
+                };
+
+                let result = harte::run_file(path, options.variant, options.keep_going, &stop);
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| a.opcode.cmp(&b.opcode));
+    results
+}
+
+/// Prints a table of the results followed by the first failure of each opcode. Returns
+/// true if anything failed.
+fn print_summary(results: &[OpcodeResult]) -> bool {
+    println!("{:<8}{:>8}{:>8}{:>8}", "Opcode", "Passed", "Failed", "Skipped");
+    for result in results {
+        println!(
+            "{:<8}{:>8}{:>8}{:>8}",
+            result.opcode, result.passed, result.failed, result.skipped
+        );
+    }
+
+    for result in results {
+        if let Some(failure) = &result.first_failure {
+            println!();
+            println!("{}: test \"{}\" failed", result.opcode, failure.name);
+            print!("{}", failure.message);
+        }
+    }
+
+    let passed = results.iter().map(|x| x.passed).sum::<usize>();
+    let failed = results.iter().map(|x| x.failed).sum::<usize>();
+    let skipped = results.iter().map(|x| x.skipped).sum::<usize>();
+
+    println!();
+    println!(
+        "{} passed, {} failed, {} skipped across {} opcodes",
+        passed,
+        failed,
+        skipped,
+        results.len()
+    );
+
+    failed > 0
+}
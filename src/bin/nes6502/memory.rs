@@ -0,0 +1,58 @@
+use nes6502::{Interrupts, Mapper};
+
+/// A flat 64KB of ram.
+pub struct Memory(pub [u8; 0x10000]);
+
+impl Memory {
+    pub fn new() -> Self {
+        Self([0; 0x10000])
+    }
+}
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0[address as usize] = byte
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0, &self.0)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0, &mut self.0)])
+    }
+}
+
+#[derive(Default)]
+pub struct InterruptsContainer {
+    pub interrupt: bool,
+    pub non_maskable_interrupt: bool,
+}
+
+impl InterruptsContainer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Interrupts for InterruptsContainer {
+    fn interrupt_state(&self) -> bool {
+        self.interrupt
+    }
+
+    fn set_interrupt_state(&mut self, new_state: bool) {
+        self.interrupt = new_state;
+    }
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        self.non_maskable_interrupt
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, new_state: bool) {
+        self.non_maskable_interrupt = new_state;
+    }
+}
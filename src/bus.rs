@@ -0,0 +1,518 @@
+//! The cpu memory map of the NES, with the devices on it supplied by the user.
+
+use crate::{Interrupts, Mapper};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// The cycles the cpu is stalled for by an OAM DMA, not counting the extra cycle taken
+/// when it starts on an odd cycle.
+pub const OAM_DMA_CYCLES: u32 = 513;
+
+/// The PPU registers at $2000-$2007. Reads have side effects on a PPU (such as clearing
+/// the vblank flag), but are made through `&self`, so the state they change has to be
+/// kept in a `Cell`.
+pub trait PpuPort {
+    /// Reads a register, where `register` is the address modulo 8.
+    fn read_register(&self, register: u8) -> u8;
+
+    /// Reads a register without side effects. Defaults to [`Self::read_register`].
+    fn peek_register(&self, register: u8) -> u8 {
+        self.read_register(register)
+    }
+
+    fn write_register(&mut self, register: u8, byte: u8);
+}
+
+/// The APU registers at $4000-$4013, $4015 and $4017.
+pub trait ApuPort {
+    /// Reads $4015, the only readable APU register. Bit 5 is open bus, so it is ignored.
+    fn read_status(&self) -> u8;
+
+    /// Reads $4015 without side effects. Defaults to [`Self::read_status`].
+    fn peek_status(&self) -> u8 {
+        self.read_status()
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8);
+}
+
+/// The controller ports at $4016 and $4017. Only bits 0-4 of a read are driven by the
+/// controllers, the rest being open bus.
+pub trait Controller {
+    /// Reads a port, where `port` is 0 for $4016 and 1 for $4017. Like
+    /// [`PpuPort::read_register`], this is made through `&self` even though it shifts the
+    /// controller.
+    fn read(&self, port: u8) -> u8;
+
+    /// Reads a port without shifting the controller. Defaults to [`Self::read`].
+    fn peek(&self, port: u8) -> u8 {
+        self.read(port)
+    }
+
+    /// Called on writes to $4016, whose bit 0 is the strobe of both ports.
+    fn write_strobe(&mut self, byte: u8);
+
+    /// Writes the state of the controllers as part of [`NesBus`]'s [`Mapper::save_state`].
+    /// The default saves nothing.
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let _ = writer;
+        Ok(())
+    }
+
+    /// Restores what was written by [`Self::save_state`], keeping the current state if it
+    /// fails like [`Mapper::load_state`].
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let _ = reader;
+        Ok(())
+    }
+}
+
+/// The cartridge space at $4020-$FFFF.
+pub trait Cartridge {
+    /// Reads a byte, or returns None if nothing is mapped at the address, in which case
+    /// the read is open bus.
+    fn read(&self, address: u16) -> Option<u8>;
+
+    /// Reads a byte without side effects. Defaults to [`Self::read`].
+    fn peek(&self, address: u16) -> Option<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u16, byte: u8);
+
+    /// Whether the cartridge is asserting the IRQ line. The line is level triggered, so this
+    /// stays true until the IRQ is acknowledged through a register of the cartridge. Defaults
+    /// to false.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Returns the battery backed PRG-RAM, which is what a save file holds. Defaults to
+    /// None, for cartridges without a battery.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// The mutable counterpart of [`Self::battery_ram`], for loading a save file.
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Writes the state of the cartridge (such as its PRG-RAM and bank registers) as part of
+    /// [`NesBus`]'s [`Mapper::save_state`]. The default saves nothing.
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let _ = writer;
+        Ok(())
+    }
+
+    /// Restores what was written by [`Self::save_state`], keeping the current state if it
+    /// fails like [`Mapper::load_state`].
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let _ = reader;
+        Ok(())
+    }
+}
+
+/// Lets the cartridge be chosen at runtime, as returned by [`crate::mappers::from_rom`].
+impl<T: Cartridge + ?Sized> Cartridge for Box<T> {
+    fn read(&self, address: u16) -> Option<u8> {
+        (**self).read(address)
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        (**self).peek(address)
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        (**self).write(address, byte)
+    }
+
+    fn irq(&self) -> bool {
+        (**self).irq()
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        (**self).battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        (**self).battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (**self).save_state(writer)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        (**self).load_state(reader)
+    }
+}
+
+/// A device that is not connected. Every read returns 0, or open bus for a cartridge, and
+/// writes are ignored.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Unconnected;
+
+impl PpuPort for Unconnected {
+    fn read_register(&self, _register: u8) -> u8 {
+        0
+    }
+
+    fn write_register(&mut self, _register: u8, _byte: u8) {}
+}
+
+impl ApuPort for Unconnected {
+    fn read_status(&self) -> u8 {
+        0
+    }
+
+    fn write_register(&mut self, _address: u16, _byte: u8) {}
+}
+
+impl Controller for Unconnected {
+    fn read(&self, _port: u8) -> u8 {
+        0
+    }
+
+    fn write_strobe(&mut self, _byte: u8) {}
+}
+
+impl Cartridge for Unconnected {
+    fn read(&self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    fn write(&mut self, _address: u16, _byte: u8) {}
+}
+
+/// The cpu memory map of the NES.
+///
+/// | Address       | Contents                                                    |
+/// |---------------|-------------------------------------------------------------|
+/// | $0000 - $1FFF | 2KB of internal ram, mirrored                               |
+/// | $2000 - $3FFF | the PPU registers, mirrored every 8 bytes                   |
+/// | $4000 - $4013 | the APU registers                                           |
+/// | $4014         | OAM DMA                                                     |
+/// | $4015         | the APU status                                              |
+/// | $4016 - $4017 | the controller ports, while writes to $4017 go to the APU   |
+/// | $4018 - $401F | the disabled APU and IO test mode                           |
+/// | $4020 - $FFFF | the cartridge                                               |
+///
+/// Reads of nothing return the last value on the data bus (open bus). Only the internal
+/// ram is part of [`Mapper::snapshot_regions`], so it is all [`crate::Cpu::state`]
+/// captures.
+///
+/// # Examples
+/// ### Booting a cartridge without any other devices.
+/// ```
+/// use nes6502::bus::NesBus;
+/// use nes6502::rom::{Nrom, Rom};
+/// use nes6502::{Cpu, Interrupts};
+///
+/// struct NoInterrupts;
+///
+/// impl Interrupts for NoInterrupts {
+///     fn interrupt_state(&self) -> bool {
+///         false
+///     }
+///
+///     fn set_interrupt_state(&mut self, _new_state: bool) {}
+///
+///     fn non_maskable_interrupt_state(&self) -> bool {
+///         false
+///     }
+///
+///     fn set_non_maskable_interrupt_state(&mut self, _new_state: bool) {}
+/// }
+///
+/// // a 16KB NROM cartridge whose reset vector points to $C000
+/// let mut ines = vec![0; 16 + 0x4000];
+/// ines[0..6].copy_from_slice(b"NES\x1A\x01\x00");
+/// ines[16 + 0x3FFD] = 0xC0;
+///
+/// let rom = Rom::from_ines(&ines).unwrap();
+/// let bus = NesBus::with_cartridge(Nrom::new(&rom).unwrap());
+/// let mut cpu = Cpu::new(bus, NoInterrupts);
+/// cpu.initialize();
+///
+/// assert_eq!(cpu.program_counter, 0xC000);
+/// ```
+#[derive(Clone, Debug)]
+pub struct NesBus<P: PpuPort, A: ApuPort, C: Controller, R: Cartridge> {
+    pub ram: [u8; 0x800],
+    pub ppu: P,
+    pub apu: A,
+    pub controller: C,
+    pub cartridge: R,
+    /// The last value on the data bus.
+    pub open_bus: Cell<u8>,
+    dma_stall: u32,
+    /// Whether [`Self::update_irq`] last asserted the IRQ line for the cartridge.
+    cartridge_irq: bool,
+}
+
+impl<P: PpuPort, A: ApuPort, C: Controller, R: Cartridge> NesBus<P, A, C, R> {
+    pub fn new(ppu: P, apu: A, controller: C, cartridge: R) -> Self {
+        Self {
+            ram: [0; 0x800],
+            ppu,
+            apu,
+            controller,
+            cartridge,
+            open_bus: Cell::new(0),
+            dma_stall: 0,
+            cartridge_irq: false,
+        }
+    }
+
+    /// Returns the cycles the cpu has to be stalled for by the OAM DMAs since the last call,
+    /// which the caller is expected to add to its cycle count.
+    pub fn take_dma_stall(&mut self) -> u32 {
+        core::mem::take(&mut self.dma_stall)
+    }
+
+    /// Drives the IRQ line from [`Cartridge::irq`], which is meant to be called after every
+    /// instruction. This keeps the line asserted until the cartridge is acknowledged, and
+    /// releases it as soon as it is, so an IRQ acknowledged while interrupts are disabled is
+    /// never serviced. The line is only released if it was asserted for the cartridge,
+    /// which leaves the other IRQ sources sharing `interrupts` alone.
+    pub fn update_irq<I: Interrupts>(&mut self, interrupts: &mut I) {
+        let irq = self.cartridge.irq();
+        if irq || self.cartridge_irq {
+            interrupts.set_interrupt_state(irq);
+        }
+
+        self.cartridge_irq = irq;
+    }
+
+    /// Copies a page of memory to $2004, as done by writing the page to $4014.
+    fn oam_dma(&mut self, page: u8) {
+        for low_byte in 0..=0xFF {
+            let byte = self.read(u16::from_le_bytes([low_byte, page]));
+            self.ppu.write_register(4, byte);
+        }
+
+        self.dma_stall += OAM_DMA_CYCLES;
+    }
+}
+
+impl<R: Cartridge> NesBus<Unconnected, Unconnected, Unconnected, R> {
+    /// Creates a bus with nothing but a cartridge, for running cpu code that does not
+    /// need the other devices.
+    pub fn with_cartridge(cartridge: R) -> Self {
+        Self::new(Unconnected, Unconnected, Unconnected, cartridge)
+    }
+}
+
+impl<P: PpuPort, A: ApuPort, C: Controller, R: Cartridge> Mapper for NesBus<P, A, C, R> {
+    fn read(&self, address: u16) -> u8 {
+        let open_bus = self.open_bus.get();
+
+        let value = match address {
+            0x0000..=0x1FFF => self.ram[address as usize & 0x7FF],
+            0x2000..=0x3FFF => self.ppu.read_register((address & 0x7) as u8),
+            // the status is read internally, so it does not change the data bus
+            0x4015 => return (self.apu.read_status() & !0x20) | (open_bus & 0x20),
+            0x4016 | 0x4017 => {
+                (self.controller.read((address & 1) as u8) & 0x1F) | (open_bus & 0xE0)
+            }
+            0x4000..=0x401F => open_bus,
+            _ => self.cartridge.read(address).unwrap_or(open_bus),
+        };
+
+        self.open_bus.set(value);
+        value
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        let open_bus = self.open_bus.get();
+
+        match address {
+            0x0000..=0x1FFF => self.ram[address as usize & 0x7FF],
+            0x2000..=0x3FFF => self.ppu.peek_register((address & 0x7) as u8),
+            0x4015 => (self.apu.peek_status() & !0x20) | (open_bus & 0x20),
+            0x4016 | 0x4017 => {
+                (self.controller.peek((address & 1) as u8) & 0x1F) | (open_bus & 0xE0)
+            }
+            0x4000..=0x401F => open_bus,
+            _ => self.cartridge.peek(address).unwrap_or(open_bus),
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.open_bus.set(byte);
+
+        match address {
+            0x0000..=0x1FFF => self.ram[address as usize & 0x7FF] = byte,
+            0x2000..=0x3FFF => self.ppu.write_register((address & 0x7) as u8, byte),
+            0x4014 => self.oam_dma(byte),
+            0x4016 => self.controller.write_strobe(byte),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(address, byte),
+            0x4018..=0x401F => {}
+            _ => self.cartridge.write(address, byte),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.ram)?;
+        writer.write_all(&[self.open_bus.get()])?;
+        Controller::save_state(&self.controller, writer)?;
+        Cartridge::save_state(&self.cartridge, writer)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut ram = [0; 0x800];
+        let mut open_bus = [0];
+        reader.read_exact(&mut ram)?;
+        reader.read_exact(&mut open_bus)?;
+
+        // the controller is loaded before the cartridge is read, so it is put back if the
+        // cartridge fails
+        let mut controller = Vec::new();
+        Controller::save_state(&self.controller, &mut controller)?;
+        Controller::load_state(&mut self.controller, reader)?;
+        if let Err(error) = Cartridge::load_state(&mut self.cartridge, reader) {
+            Controller::load_state(&mut self.controller, &mut controller.as_slice())?;
+            return Err(error);
+        }
+
+        self.ram = ram;
+        self.open_bus.set(open_bus[0]);
+        Ok(())
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0x0000, &self.ram)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0x0000, &mut self.ram)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::{Nrom, Rom, INES_MAGIC};
+
+    #[derive(Default)]
+    struct Ppu {
+        registers: [u8; 8],
+        oam: Vec<u8>,
+    }
+
+    impl PpuPort for Ppu {
+        fn read_register(&self, register: u8) -> u8 {
+            self.registers[register as usize]
+        }
+
+        fn write_register(&mut self, register: u8, byte: u8) {
+            self.registers[register as usize] = byte;
+            if register == 4 {
+                self.oam.push(byte);
+            }
+        }
+    }
+
+    struct Joypad;
+
+    impl Controller for Joypad {
+        fn read(&self, port: u8) -> u8 {
+            0xFE | (port ^ 1)
+        }
+
+        fn write_strobe(&mut self, _byte: u8) {}
+    }
+
+    fn nrom() -> Nrom {
+        let mut bytes = vec![0; 16 + 0x4000];
+        bytes[0..4].copy_from_slice(&INES_MAGIC);
+        bytes[4] = 1;
+        bytes[16] = 0xEA;
+
+        Nrom::new(&Rom::from_ines(&bytes).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut bus = NesBus::new(Ppu::default(), Unconnected, Unconnected, nrom());
+
+        bus.write(0x1801, 0x42);
+        assert_eq!(bus.read(0x0001), 0x42);
+
+        bus.write(0x3FFD, 0x24);
+        assert_eq!(bus.ppu.registers[5], 0x24);
+        assert_eq!(bus.read(0x2005), 0x24);
+
+        assert_eq!(bus.read(0xC000), 0xEA);
+    }
+
+    #[test]
+    fn test_open_bus() {
+        let mut bus = NesBus::new(Ppu::default(), Unconnected, Joypad, nrom());
+
+        // $4020-$5FFF is not mapped by NROM
+        bus.write(0x0000, 0xA5);
+        assert_eq!(bus.read(0x0000), 0xA5);
+        assert_eq!(bus.read(0x5000), 0xA5);
+        assert_eq!(bus.read(0x4018), 0xA5);
+
+        // only bits 0-4 come from the controller
+        assert_eq!(bus.read(0x4016), 0xBF);
+        assert_eq!(bus.read(0x4017), 0xBE);
+
+        // the APU status does not change the data bus
+        bus.write(0x0000, 0x20);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4015), 0x20);
+        assert_eq!(bus.peek(0x4018), 0x20);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut bus = NesBus::new(Ppu::default(), Unconnected, Unconnected, nrom());
+        for (i, byte) in bus.ram[0x200..0x300].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        bus.write(0x4014, 0x02);
+        assert_eq!(bus.ppu.oam, (0..=0xFF).collect::<Vec<u8>>());
+        assert_eq!(bus.take_dma_stall(), OAM_DMA_CYCLES);
+        assert_eq!(bus.take_dma_stall(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_truncated_state() {
+        use crate::controller::{Buttons, Joypads};
+
+        let mut bus = NesBus::new(Ppu::default(), Unconnected, Joypads::new(), nrom());
+        bus.write(0x0000, 0x42);
+        bus.controller.set_buttons(0, Buttons::A);
+        let mut state = Vec::new();
+        bus.save_state(&mut state).unwrap();
+
+        bus.write(0x0000, 0x24);
+        bus.controller.set_buttons(0, Buttons::B);
+        state.pop();
+        assert!(bus.load_state(&mut state.as_slice()).is_err());
+        assert_eq!(bus.ram[0], 0x24);
+        assert_eq!(bus.controller.ports[0].buttons(), Buttons::B);
+
+        state.truncate(0x800);
+        assert!(bus.load_state(&mut state.as_slice()).is_err());
+        assert_eq!(bus.ram[0], 0x24);
+    }
+}
@@ -0,0 +1,326 @@
+use alloc::vec::Vec;
+
+/// The most frames that are tracked at once. Each frame needs at least 2 bytes of the
+/// 256 byte hardware stack, so this can only be exceeded when frames are abandoned without
+/// the stack pointer showing it. When full, the outermost frame is dropped.
+pub const CALL_STACK_CAPACITY: usize = 128;
+
+/// What pushed a frame onto the call stack.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum FrameKind {
+    #[default]
+    Jsr,
+    Irq,
+    Nmi,
+    Brk,
+}
+
+impl FrameKind {
+    /// Returns true if the frame is returned from with RTI rather than RTS.
+    pub fn is_interrupt(&self) -> bool {
+        !matches!(self, FrameKind::Jsr)
+    }
+}
+
+/// A single entry of the shadow call stack.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct CallFrame {
+    /// The address of the JSR or BRK instruction. For hardware interrupts, this is the address
+    /// of the instruction that was about to run.
+    pub caller: u16,
+    /// The address that was jumped to.
+    pub target: u16,
+    pub kind: FrameKind,
+    /// The stack pointer before anything was pushed for this frame.
+    pub stack_pointer: u8,
+}
+
+impl CallFrame {
+    /// The address execution should continue at once the frame returns.
+    pub fn return_address(&self) -> u16 {
+        match self.kind {
+            FrameKind::Jsr => self.caller.wrapping_add(3),
+            // BRK skips over its debugging byte
+            FrameKind::Brk => self.caller.wrapping_add(2),
+            FrameKind::Irq | FrameKind::Nmi => self.caller,
+        }
+    }
+}
+
+/// A disagreement between the shadow call stack and what the program did with the
+/// hardware stack.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum StackMismatch {
+    /// An RTS or RTI at `address` did not return from any tracked frame, such as when
+    /// an address is pushed by hand and RTS is used as a jump.
+    UnmatchedReturn { address: u16, stack_pointer: u8 },
+    /// Frames were dropped because the stack pointer moved past them without them
+    /// returning, such as when a PLA/PLA pair discards a return address.
+    AbandonedFrames { address: u16, count: usize },
+    /// A frame was returned from, but with the wrong instruction or to a different address
+    /// than it was called from, such as when the return address on the stack is modified
+    /// to skip inline data.
+    WrongReturn {
+        address: u16,
+        frame: CallFrame,
+        returned_to: u16,
+    },
+}
+
+/// A shadow of the hardware stack that only tracks calls, maintained by JSR, RTS, BRK,
+/// RTI and interrupts. Enable it with [`crate::Cpu::enable_call_stack`]. The frames are
+/// kept on the heap, so a cpu without a call stack does not carry them.
+#[derive(Clone, Debug)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+    mismatch_count: u32,
+    last_mismatch: Option<StackMismatch>,
+}
+
+impl Default for CallStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::with_capacity(CALL_STACK_CAPACITY),
+            mismatch_count: 0,
+            last_mismatch: None,
+        }
+    }
+
+    /// The active frames, outermost first.
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// The amount of mismatches detected since the call stack was enabled.
+    pub fn mismatch_count(&self) -> u32 {
+        self.mismatch_count
+    }
+
+    /// The most recently detected mismatch.
+    pub fn last_mismatch(&self) -> Option<StackMismatch> {
+        self.last_mismatch
+    }
+
+    /// Drops every frame, which is what a reset does.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Records a call. `address` is the instruction making the call.
+    pub(crate) fn push(&mut self, address: u16, frame: CallFrame) {
+        // a live frame always has a return address above the current stack pointer
+        self.discard_abandoned(address, frame.stack_pointer.wrapping_add(1));
+
+        if self.frames.len() == CALL_STACK_CAPACITY {
+            self.frames.remove(0);
+        }
+
+        self.frames.push(frame);
+    }
+
+    /// Records a return by the RTS or RTI at `address`. `stack_pointer` is the stack pointer
+    /// after the return address was popped.
+    pub(crate) fn pop(
+        &mut self,
+        address: u16,
+        from_interrupt: bool,
+        stack_pointer: u8,
+        returned_to: u16,
+    ) {
+        self.discard_abandoned(address, stack_pointer);
+
+        let frame = match self.frames().last() {
+            Some(frame) if frame.stack_pointer == stack_pointer => *frame,
+            _ => {
+                self.record(StackMismatch::UnmatchedReturn {
+                    address,
+                    stack_pointer,
+                });
+                return;
+            }
+        };
+
+        self.frames.pop();
+
+        if frame.kind.is_interrupt() != from_interrupt || frame.return_address() != returned_to {
+            self.record(StackMismatch::WrongReturn {
+                address,
+                frame,
+                returned_to,
+            });
+        }
+    }
+
+    /// Drops the frames whose stack pointer is below `stack_pointer`, as their return
+    /// addresses can no longer be on the stack.
+    fn discard_abandoned(&mut self, address: u16, stack_pointer: u8) {
+        let live = self
+            .frames()
+            .iter()
+            .take_while(|frame| frame.stack_pointer >= stack_pointer)
+            .count();
+
+        if live != self.frames.len() {
+            let count = self.frames.len() - live;
+            self.frames.truncate(live);
+            self.record(StackMismatch::AbandonedFrames { address, count });
+        }
+    }
+
+    fn record(&mut self, mismatch: StackMismatch) {
+        self.mismatch_count = self.mismatch_count.saturating_add(1);
+        self.last_mismatch = Some(mismatch);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{InterruptsContainer, Memory};
+    use crate::Cpu;
+
+    fn jsr(caller: u16, stack_pointer: u8) -> CallFrame {
+        CallFrame {
+            caller,
+            target: 0x9000,
+            kind: FrameKind::Jsr,
+            stack_pointer,
+        }
+    }
+
+    #[test]
+    fn test_balanced_calls() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(0x8000, jsr(0x8000, 0xFD));
+        call_stack.push(0x9000, jsr(0x9000, 0xFB));
+        assert_eq!(call_stack.frames().len(), 2);
+
+        call_stack.pop(0x9100, false, 0xFB, 0x9003);
+        call_stack.pop(0x9010, false, 0xFD, 0x8003);
+
+        assert!(call_stack.frames().is_empty());
+        assert_eq!(call_stack.mismatch_count(), 0);
+    }
+
+    #[test]
+    fn test_pla_pla() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(0x8000, jsr(0x8000, 0xFD));
+        call_stack.push(0x9000, jsr(0x9000, 0xFB));
+
+        // the inner subroutine drops its return address and returns to the outer caller
+        call_stack.pop(0x9100, false, 0xFD, 0x8003);
+
+        assert!(call_stack.frames().is_empty());
+        assert_eq!(call_stack.mismatch_count(), 1);
+        assert_eq!(
+            call_stack.last_mismatch(),
+            Some(StackMismatch::AbandonedFrames {
+                address: 0x9100,
+                count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_rts_jump() {
+        let mut call_stack = CallStack::new();
+        call_stack.push(0x8000, jsr(0x8000, 0xFD));
+
+        // two bytes pushed by hand and then returned to
+        call_stack.pop(0x9000, false, 0xFB, 0xA000);
+
+        assert_eq!(call_stack.frames().len(), 1);
+        assert_eq!(
+            call_stack.last_mismatch(),
+            Some(StackMismatch::UnmatchedReturn {
+                address: 0x9000,
+                stack_pointer: 0xFB
+            })
+        );
+    }
+
+    #[test]
+    fn test_cpu_calls() {
+        let mut memory = Memory([0; 0x10000]);
+        let mut load = |address: usize, bytes: &[u8]| {
+            memory.0[address..address + bytes.len()].copy_from_slice(bytes)
+        };
+        load(0x8000, &[0x20, 0x00, 0x90]); // JSR $9000
+        load(0x9000, &[0x20, 0x00, 0xA0, 0x60]); // JSR $A000, RTS
+        load(0xA000, &[0x00, 0x00, 0x58, 0xEA, 0x60]); // BRK, CLI, NOP, RTS
+        load(0xB000, &[0x40]); // RTI
+        load(0xFFFE, &[0x00, 0xB0]);
+
+        let mut cpu = Cpu::new(memory, InterruptsContainer::default());
+        cpu.initialized = true;
+        cpu.program_counter = 0x8000;
+        cpu.stack_pointer = 0xFD;
+        cpu.processor_status.0 = 0x24;
+        cpu.enable_call_stack();
+
+        let kinds = |cpu: &Cpu<Memory, InterruptsContainer>| -> Vec<FrameKind> {
+            cpu.backtrace().iter().map(|frame| frame.kind).collect()
+        };
+
+        // JSR, JSR, BRK
+        for _ in 0..3 {
+            cpu.cycle();
+        }
+        assert_eq!(
+            kinds(&cpu),
+            [FrameKind::Jsr, FrameKind::Jsr, FrameKind::Brk]
+        );
+        assert_eq!(
+            cpu.backtrace()[1],
+            CallFrame {
+                caller: 0x9000,
+                target: 0xA000,
+                kind: FrameKind::Jsr,
+                stack_pointer: 0xFB,
+            }
+        );
+        assert_eq!(
+            cpu.backtrace()[2],
+            CallFrame {
+                caller: 0xA000,
+                target: 0xB000,
+                kind: FrameKind::Brk,
+                stack_pointer: 0xF9,
+            }
+        );
+
+        // RTI, CLI and then the IRQ is serviced before the NOP
+        cpu.cycle();
+        cpu.cycle();
+        cpu.interrupts.interrupt = true;
+        cpu.cycle();
+        assert_eq!(
+            kinds(&cpu),
+            [FrameKind::Jsr, FrameKind::Jsr, FrameKind::Irq]
+        );
+        assert_eq!(
+            cpu.backtrace()[2],
+            CallFrame {
+                caller: 0xA003,
+                target: 0xB000,
+                kind: FrameKind::Irq,
+                stack_pointer: 0xF9,
+            }
+        );
+
+        // RTI, NOP, RTS, RTS
+        for _ in 0..4 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.program_counter, 0x8003);
+        assert!(cpu.backtrace().is_empty());
+        assert_eq!(cpu.call_stack.unwrap().mismatch_count(), 0);
+    }
+}
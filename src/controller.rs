@@ -0,0 +1,284 @@
+//! The standard NES controller, which plugs into the controller ports of a
+//! [`NesBus`](crate::bus::NesBus).
+
+use crate::bus::Controller;
+use core::cell::Cell;
+use core::ops::BitOr;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// The buttons held on a standard controller, one bit each in the order they are read.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Buttons(pub u8);
+
+impl Buttons {
+    pub const NONE: Buttons = Buttons(0);
+    pub const A: Buttons = Buttons(0b0000_0001);
+    pub const B: Buttons = Buttons(0b0000_0010);
+    pub const SELECT: Buttons = Buttons(0b0000_0100);
+    pub const START: Buttons = Buttons(0b0000_1000);
+    pub const UP: Buttons = Buttons(0b0001_0000);
+    pub const DOWN: Buttons = Buttons(0b0010_0000);
+    pub const LEFT: Buttons = Buttons(0b0100_0000);
+    pub const RIGHT: Buttons = Buttons(0b1000_0000);
+
+    /// Returns true if every button in `buttons` is held.
+    pub fn contains(&self, buttons: Buttons) -> bool {
+        self.0 & buttons.0 == buttons.0
+    }
+}
+
+impl BitOr for Buttons {
+    type Output = Buttons;
+
+    fn bitor(self, rhs: Buttons) -> Buttons {
+        Buttons(self.0 | rhs.0)
+    }
+}
+
+/// A standard controller, which is a shift register loaded with the buttons while the
+/// strobe is high.
+///
+/// Each read returns the next button in bit 0, and once all 8 have been read it returns 1
+/// like an official controller. While the strobe is high the buttons are reloaded
+/// continuously, so every read returns the A button.
+#[derive(Clone, Debug, Default)]
+pub struct Joypad {
+    buttons: Buttons,
+    strobe: bool,
+    /// Reads go through `&self`, so the shift register is kept in a `Cell`.
+    shift_register: Cell<u8>,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn buttons(&self) -> Buttons {
+        self.buttons
+    }
+
+    /// Sets the buttons that will be latched by the next strobe, or right away if the strobe
+    /// is high.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.buttons = buttons;
+        if self.strobe {
+            self.shift_register.set(buttons.0);
+        }
+    }
+
+    /// Reads the next bit and shifts the register, filling it with 1s.
+    pub fn read(&self) -> u8 {
+        let bit = self.peek();
+        if !self.strobe {
+            self.shift_register.set((self.shift_register.get() >> 1) | 0x80);
+        }
+
+        bit
+    }
+
+    /// Reads the next bit without shifting the register.
+    pub fn peek(&self) -> u8 {
+        match self.strobe {
+            true => self.buttons.0 & 1,
+            false => self.shift_register.get() & 1,
+        }
+    }
+
+    pub fn write_strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.shift_register.set(self.buttons.0);
+        }
+    }
+}
+
+/// A standard controller in each of the two ports, with $4016 reading the first and $4017
+/// the second. Only bit 0 is driven, as [`NesBus`](crate::bus::NesBus) fills in the open
+/// bus bits.
+///
+/// On NTSC consoles a DMC sample fetch that lands on a read of a port reads it again,
+/// skipping a button. Games work around this by polling until two reads agree, and an APU
+/// can reproduce it by calling [`Joypads::dmc_conflict`].
+///
+/// # Examples
+/// ```
+/// use nes6502::bus::{NesBus, Unconnected};
+/// use nes6502::controller::{Buttons, Joypads};
+/// use nes6502::Mapper;
+///
+/// let mut bus = NesBus::new(Unconnected, Unconnected, Joypads::new(), Unconnected);
+/// bus.controller.set_buttons(0, Buttons::A | Buttons::START);
+///
+/// // latch the buttons, then read A, B, Select and Start
+/// bus.write(0x4016, 1);
+/// bus.write(0x4016, 0);
+/// let bits: Vec<u8> = (0..4).map(|_| bus.read(0x4016) & 1).collect();
+/// assert_eq!(bits, [1, 0, 0, 1]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Joypads {
+    pub ports: [Joypad; 2],
+}
+
+impl Joypads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the buttons of the controller in `port`, which is 0 or 1.
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        self.ports[port].set_buttons(buttons);
+    }
+
+    /// Shifts a port an extra time, as a DMC sample fetch does when it lands on a read of
+    /// the port. `port` is 0 for $4016 and 1 for $4017.
+    pub fn dmc_conflict(&self, port: u8) {
+        self.ports[port as usize & 1].read();
+    }
+}
+
+impl Controller for Joypads {
+    fn read(&self, port: u8) -> u8 {
+        self.ports[port as usize & 1].read()
+    }
+
+    fn peek(&self, port: u8) -> u8 {
+        self.ports[port as usize & 1].peek()
+    }
+
+    fn write_strobe(&mut self, byte: u8) {
+        for joypad in &mut self.ports {
+            joypad.write_strobe(byte & 1 != 0);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for joypad in &self.ports {
+            writer.write_all(&[
+                joypad.buttons.0,
+                joypad.strobe as u8,
+                joypad.shift_register.get(),
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut bytes = [0; 6];
+        reader.read_exact(&mut bytes)?;
+
+        for (joypad, bytes) in self.ports.iter_mut().zip(bytes.chunks_exact(3)) {
+            joypad.buttons = Buttons(bytes[0]);
+            joypad.strobe = bytes[1] != 0;
+            joypad.shift_register.set(bytes[2]);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::{NesBus, Unconnected};
+    use crate::rom::{Nrom, Rom, INES_MAGIC};
+    use crate::test_util::NoInterrupts;
+    use crate::Cpu;
+
+    #[test]
+    fn test_joypad() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(Buttons::A | Buttons::DOWN);
+
+        // nothing is latched until the strobe
+        assert_eq!(joypad.read(), 0);
+
+        // every read returns A while the strobe is high
+        joypad.write_strobe(true);
+        assert_eq!([joypad.read(), joypad.read()], [1, 1]);
+        joypad.set_buttons(Buttons::B);
+        assert_eq!(joypad.read(), 0);
+        joypad.set_buttons(Buttons::A | Buttons::DOWN);
+        joypad.write_strobe(false);
+
+        let bits: Vec<u8> = (0..10).map(|_| joypad.read()).collect();
+        assert_eq!(bits, [1, 0, 0, 0, 0, 1, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_dmc_conflict() {
+        let mut joypads = Joypads::new();
+        joypads.set_buttons(1, Buttons::A | Buttons::SELECT);
+        joypads.write_strobe(1);
+        joypads.write_strobe(0);
+
+        // the extra read loses the A button
+        joypads.dmc_conflict(1);
+        assert_eq!(joypads.peek(1), 0);
+        assert_eq!([joypads.read(1), joypads.read(1)], [0, 1]);
+        assert_eq!(joypads.read(0), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_truncated_state() {
+        let mut joypads = Joypads::new();
+        joypads.set_buttons(0, Buttons::A);
+        joypads.set_buttons(1, Buttons::B);
+        let mut state = Vec::new();
+        joypads.save_state(&mut state).unwrap();
+
+        // neither port is restored from a partial state
+        joypads.set_buttons(0, Buttons::START);
+        joypads.set_buttons(1, Buttons::SELECT);
+        assert!(joypads.load_state(&mut &state[..5]).is_err());
+        assert_eq!(joypads.ports[0].buttons(), Buttons::START);
+        assert_eq!(joypads.ports[1].buttons(), Buttons::SELECT);
+    }
+
+    #[test]
+    fn test_polling() {
+        // strobes the controller and stores 10 reads of $4016 at $00
+        let program = [
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA2, 0x00, // LDX #$00
+            0xAD, 0x16, 0x40, // LDA $4016
+            0x95, 0x00, // STA $00,X
+            0xE8, // INX
+            0xE0, 0x0A, // CPX #$0A
+            0xD0, 0xF6, // BNE $C00C
+            0x4C, 0x16, 0xC0, // JMP $C016
+        ];
+
+        let mut bytes = vec![0; 16 + 0x4000];
+        bytes[0..4].copy_from_slice(&INES_MAGIC);
+        bytes[4] = 1;
+        bytes[16..16 + program.len()].copy_from_slice(&program);
+        bytes[16 + 0x3FFD] = 0xC0;
+
+        let cartridge = Nrom::new(&Rom::from_ines(&bytes).unwrap()).unwrap();
+        let mut bus = NesBus::new(Unconnected, Unconnected, Joypads::new(), cartridge);
+        bus.controller.set_buttons(0, Buttons::START | Buttons::RIGHT);
+
+        let mut cpu = Cpu::new(bus, NoInterrupts);
+        cpu.initialize();
+        while cpu.program_counter != 0xC016 {
+            cpu.cycle();
+        }
+
+        // the upper bits are the high byte of $4016 left on the data bus
+        assert_eq!(
+            cpu.memory_mapper.ram[..10],
+            [0x40, 0x40, 0x40, 0x41, 0x40, 0x40, 0x40, 0x41, 0x41, 0x41]
+        );
+    }
+}
@@ -0,0 +1,612 @@
+//! A minimal GDB remote serial protocol server for the cpu.
+//!
+//! GDB has no builtin 6502 architecture, so the register file is exposed in the
+//! following order (the `g` packet), all values being little-endian:
+//!
+//! | Number | Register | Size    |
+//! |--------|----------|---------|
+//! | 0      | A        | 1 byte  |
+//! | 1      | X        | 1 byte  |
+//! | 2      | Y        | 1 byte  |
+//! | 3      | SP       | 1 byte  |
+//! | 4      | PC       | 2 bytes |
+//! | 5      | P        | 1 byte  |
+//!
+//! Memory is read through [`Mapper::peek`] so inspecting registers of a device will
+//! not disturb it, and written through [`Mapper::write`].
+//!
+//! # Examples
+//! ```no_run
+//! # use nes6502::{Cpu, Interrupts, Mapper};
+//! # struct Memory([u8; 0x10000]);
+//! # impl Mapper for Memory {
+//! #     fn read(&self, address: u16) -> u8 { self.0[address as usize] }
+//! #     fn write(&mut self, address: u16, byte: u8) { self.0[address as usize] = byte }
+//! # }
+//! # struct NoInterrupts;
+//! # impl Interrupts for NoInterrupts {
+//! #     fn interrupt_state(&self) -> bool { false }
+//! #     fn set_interrupt_state(&mut self, _: bool) {}
+//! #     fn non_maskable_interrupt_state(&self) -> bool { false }
+//! #     fn set_non_maskable_interrupt_state(&mut self, _: bool) {}
+//! # }
+//! use nes6502::gdbstub::GdbStub;
+//!
+//! let mut cpu = Cpu::new(Memory([0; 0x10000]), NoInterrupts);
+//! cpu.initialize();
+//!
+//! // Blocks until a debugger connects and then serves it until it detaches.
+//! GdbStub::new(&mut cpu).listen("127.0.0.1:9001").unwrap();
+//! ```
+
+use crate::{Cpu, Interrupts, Mapper, Observer};
+use std::collections::BTreeSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// The amount of instructions ran between checks for a break request from the debugger
+/// while continuing.
+const INTERRUPT_POLL_INTERVAL: u32 = 1024;
+
+/// The packet size advertised to the debugger. Memory reads are clamped to fit in it, as
+/// each byte takes two hex digits.
+const PACKET_SIZE: usize = 0x4000;
+
+/// The reason the cpu stopped, reported to the debugger as a signal number.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum StopReason {
+    /// A breakpoint was hit or a single step finished.
+    Trap,
+    /// The debugger asked us to stop.
+    Interrupt,
+    /// The next instruction is not a valid opcode.
+    IllegalInstruction,
+}
+
+impl StopReason {
+    fn signal(&self) -> u8 {
+        match self {
+            StopReason::Interrupt => 2,
+            StopReason::IllegalInstruction => 4,
+            StopReason::Trap => 5,
+        }
+    }
+}
+
+/// Serves a single debugger connection for a [`Cpu`].
+pub struct GdbStub<'a, M: Mapper, I: Interrupts, O: Observer> {
+    cpu: &'a mut Cpu<M, I, O>,
+    breakpoints: BTreeSet<u16>,
+    no_ack_mode: bool,
+}
+
+impl<'a, M: Mapper, I: Interrupts, O: Observer> GdbStub<'a, M, I, O> {
+    pub fn new(cpu: &'a mut Cpu<M, I, O>) -> Self {
+        Self {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            no_ack_mode: false,
+        }
+    }
+
+    /// Adds a software breakpoint. Returns false if it was already set.
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes a software breakpoint. Returns false if it was not set.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Waits for a single debugger to connect on the given address and serves it until
+    /// it detaches or kills the session.
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    /// Serves an already connected debugger until it detaches or kills the session.
+    pub fn serve(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+
+        while let Some(packet) = self.read_packet(&mut stream)? {
+            let response = match self.handle_packet(&packet, &mut stream)? {
+                Some(x) => x,
+                None => return Ok(()),
+            };
+
+            self.write_packet(&mut stream, &response)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles a single packet and returns the response. Returns None if the session
+    /// should end.
+    fn handle_packet(
+        &mut self,
+        packet: &[u8],
+        stream: &mut TcpStream,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let (command, arguments) = match packet.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            None => return Ok(Some(Vec::new())),
+        };
+
+        let response = match command {
+            b'?' => stop_reply(StopReason::Trap),
+            b'g' => encode_hex(&self.register_bytes()),
+            b'G' => match decode_hex(arguments) {
+                Some(bytes) if bytes.len() == 7 => {
+                    self.set_register_bytes(&bytes);
+                    b"OK".to_vec()
+                }
+                _ => error_reply(),
+            },
+            b'p' => match parse_hex(arguments) {
+                Some(register) => match self.register(register) {
+                    Some(bytes) => encode_hex(&bytes),
+                    None => error_reply(),
+                },
+                None => error_reply(),
+            },
+            b'P' => match split_once(arguments, b'=') {
+                Some((register, value)) => match (parse_hex(register), decode_hex(value)) {
+                    (Some(register), Some(value)) if self.set_register(register, &value) => {
+                        b"OK".to_vec()
+                    }
+                    _ => error_reply(),
+                },
+                None => error_reply(),
+            },
+            // the debugger asks for the rest again when given fewer bytes
+            b'm' => match parse_address_length(arguments) {
+                Some((address, length)) => {
+                    let bytes = (0..length.min(PACKET_SIZE / 2))
+                        .map(|offset| self.cpu.peek(address.wrapping_add(offset as u16)))
+                        .collect::<Vec<u8>>();
+                    encode_hex(&bytes)
+                }
+                None => error_reply(),
+            },
+            b'M' => match split_once(arguments, b':') {
+                Some((header, data)) => match (parse_address_length(header), decode_hex(data)) {
+                    (Some((address, length)), Some(bytes)) if bytes.len() == length => {
+                        for (offset, byte) in bytes.into_iter().enumerate() {
+                            self.cpu
+                                .memory_mapper
+                                .write(address.wrapping_add(offset as u16), byte);
+                        }
+                        b"OK".to_vec()
+                    }
+                    _ => error_reply(),
+                },
+                None => error_reply(),
+            },
+            // only software breakpoints are supported
+            b'Z' | b'z' => match parse_breakpoint(arguments) {
+                Some(address) => {
+                    match command == b'Z' {
+                        true => self.add_breakpoint(address),
+                        false => self.remove_breakpoint(address),
+                    };
+                    b"OK".to_vec()
+                }
+                None => Vec::new(),
+            },
+            b's' => {
+                self.resume_at(arguments);
+                stop_reply(self.step())
+            }
+            b'c' => {
+                self.resume_at(arguments);
+                stop_reply(self.resume(stream)?)
+            }
+            b'H' | b'T' => b"OK".to_vec(),
+            b'D' => {
+                self.write_packet(stream, b"OK")?;
+                return Ok(None);
+            }
+            // a kill gets no reply
+            b'k' => return Ok(None),
+            b'q' | b'Q' => self.handle_query(packet),
+            // everything else is reported as unsupported
+            _ => Vec::new(),
+        };
+
+        Ok(Some(response))
+    }
+
+    fn handle_query(&mut self, packet: &[u8]) -> Vec<u8> {
+        if packet.starts_with(b"qSupported") {
+            format!("PacketSize={:x};QStartNoAckMode+", PACKET_SIZE).into_bytes()
+        } else if packet == b"QStartNoAckMode" {
+            self.no_ack_mode = true;
+            b"OK".to_vec()
+        } else if packet == b"qAttached" {
+            b"1".to_vec()
+        } else if packet == b"qC" {
+            b"QC1".to_vec()
+        } else if packet == b"qfThreadInfo" {
+            b"m1".to_vec()
+        } else if packet == b"qsThreadInfo" {
+            b"l".to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Handles the optional resume address of the `s` and `c` packets.
+    fn resume_at(&mut self, arguments: &[u8]) {
+        if let Some(address) = parse_hex(arguments) {
+            self.cpu.program_counter = address as u16;
+        }
+    }
+
+    /// Runs a single instruction.
+    fn step(&mut self) -> StopReason {
+        if self.next_instruction_illegal() {
+            return StopReason::IllegalInstruction;
+        }
+
+        self.cpu.cycle();
+        StopReason::Trap
+    }
+
+    /// Runs until a breakpoint is hit, an illegal instruction is reached, or the debugger
+    /// sends a break request.
+    fn resume(&mut self, stream: &mut TcpStream) -> io::Result<StopReason> {
+        let mut instructions_since_poll = 0;
+
+        loop {
+            if self.step() == StopReason::IllegalInstruction {
+                return Ok(StopReason::IllegalInstruction);
+            }
+
+            if self.breakpoints.contains(&self.cpu.program_counter) {
+                return Ok(StopReason::Trap);
+            }
+
+            instructions_since_poll += 1;
+            if instructions_since_poll == INTERRUPT_POLL_INTERVAL {
+                instructions_since_poll = 0;
+
+                if break_requested(stream)? {
+                    return Ok(StopReason::Interrupt);
+                }
+            }
+        }
+    }
+
+    /// Returns true if the cpu would try to execute an illegal opcode on the next cycle.
+    /// Pending interrupts are serviced before fetching, so they are never illegal.
+    fn next_instruction_illegal(&self) -> bool {
+        let interrupt_pending = self.cpu.interrupts.non_maskable_interrupt_state()
+            || (self.cpu.interrupts.interrupt_state()
+                && !self.cpu.processor_status.interrupt_disable_flag());
+
+        // decoded as the variant of the cpu, so the 65C02 instructions are not illegal
+        !interrupt_pending && self.cpu.disassemble(self.cpu.program_counter).is_none()
+    }
+
+    fn register_bytes(&self) -> [u8; 7] {
+        let [pc_low, pc_high] = self.cpu.program_counter.to_le_bytes();
+
+        [
+            self.cpu.accumulator,
+            self.cpu.x,
+            self.cpu.y,
+            self.cpu.stack_pointer,
+            pc_low,
+            pc_high,
+            self.cpu.processor_status.0,
+        ]
+    }
+
+    fn set_register_bytes(&mut self, bytes: &[u8]) {
+        self.cpu.accumulator = bytes[0];
+        self.cpu.x = bytes[1];
+        self.cpu.y = bytes[2];
+        self.cpu.stack_pointer = bytes[3];
+        self.cpu.program_counter = u16::from_le_bytes([bytes[4], bytes[5]]);
+        self.cpu.processor_status.0 = bytes[6];
+    }
+
+    fn register(&self, register: usize) -> Option<Vec<u8>> {
+        let bytes = self.register_bytes();
+
+        Some(match register {
+            0..=3 => vec![bytes[register]],
+            4 => vec![bytes[4], bytes[5]],
+            5 => vec![bytes[6]],
+            _ => return None,
+        })
+    }
+
+    /// Returns false if the register does not exist or the value has the wrong size.
+    fn set_register(&mut self, register: usize, value: &[u8]) -> bool {
+        match (register, value) {
+            (0, [byte]) => self.cpu.accumulator = *byte,
+            (1, [byte]) => self.cpu.x = *byte,
+            (2, [byte]) => self.cpu.y = *byte,
+            (3, [byte]) => self.cpu.stack_pointer = *byte,
+            (4, [low, high]) => self.cpu.program_counter = u16::from_le_bytes([*low, *high]),
+            (5, [byte]) => self.cpu.processor_status.0 = *byte,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Reads the next packet, handling acknowledgements. Returns None if the debugger
+    /// closed the connection.
+    fn read_packet(&mut self, stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // skip everything up until the start of a packet, which includes acknowledgements
+            // and stray break requests
+            match read_byte(stream)? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut packet = Vec::new();
+            loop {
+                match read_byte(stream)? {
+                    Some(b'#') => break,
+                    Some(byte) => packet.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let checksum = match (read_byte(stream)?, read_byte(stream)?) {
+                (Some(high), Some(low)) => decode_hex(&[high, low]).map(|x| x[0]),
+                _ => return Ok(None),
+            };
+
+            if self.no_ack_mode {
+                return Ok(Some(packet));
+            }
+
+            match checksum == Some(calculate_checksum(&packet)) {
+                true => {
+                    stream.write_all(b"+")?;
+                    return Ok(Some(packet));
+                }
+                // ask the debugger to retransmit
+                false => stream.write_all(b"-")?,
+            }
+        }
+    }
+
+    fn write_packet(&mut self, stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.push(b'#');
+        packet.extend_from_slice(&encode_hex(&[calculate_checksum(data)]));
+
+        stream.write_all(&packet)?;
+        stream.flush()
+    }
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Checks without blocking whether the debugger sent a break request (0x03).
+fn break_requested(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+
+    let mut byte = [0];
+    let result = match stream.peek(&mut byte) {
+        Ok(0) => Ok(false),
+        Ok(_) if byte[0] == 0x03 => {
+            stream.read_exact(&mut byte)?;
+            Ok(true)
+        }
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+
+    stream.set_nonblocking(false)?;
+    result
+}
+
+fn stop_reply(reason: StopReason) -> Vec<u8> {
+    let mut reply = b"S".to_vec();
+    reply.extend_from_slice(&encode_hex(&[reason.signal()]));
+    reply
+}
+
+fn error_reply() -> Vec<u8> {
+    b"E01".to_vec()
+}
+
+fn calculate_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> Vec<u8> {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    bytes
+        .iter()
+        .flat_map(|byte| [DIGITS[(byte >> 4) as usize], DIGITS[(byte & 0xF) as usize]])
+        .collect()
+}
+
+fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    hex.chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some(((high << 4) | low) as u8)
+        })
+        .collect()
+}
+
+fn parse_hex(hex: &[u8]) -> Option<usize> {
+    if hex.is_empty() {
+        return None;
+    }
+
+    usize::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+fn split_once(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let index = bytes.iter().position(|x| *x == separator)?;
+    Some((&bytes[..index], &bytes[index + 1..]))
+}
+
+/// Parses the `addr,length` form used by the memory packets.
+fn parse_address_length(arguments: &[u8]) -> Option<(u16, usize)> {
+    let (address, length) = split_once(arguments, b',')?;
+    Some((u16::try_from(parse_hex(address)?).ok()?, parse_hex(length)?))
+}
+
+/// Parses the `type,addr,kind` form of the breakpoint packets. Returns None for anything
+/// other than a software breakpoint.
+fn parse_breakpoint(arguments: &[u8]) -> Option<u16> {
+    let (kind, rest) = split_once(arguments, b',')?;
+    if kind != b"0" {
+        return None;
+    }
+
+    let (address, _) = split_once(rest, b',')?;
+    u16::try_from(parse_hex(address)?).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{Memory, NoInterrupts};
+    use crate::Variant;
+    use std::thread;
+
+    /// Sends a packet and returns the response of the stub.
+    fn request(stream: &mut TcpStream, data: &str) -> String {
+        let checksum = calculate_checksum(data.as_bytes());
+        write!(stream, "${}#{:02x}", data, checksum).unwrap();
+
+        let mut ack = [0];
+        stream.read_exact(&mut ack).unwrap();
+        assert_eq!(ack[0], b'+');
+
+        let mut response = Vec::new();
+        loop {
+            let byte = read_byte(stream).unwrap().unwrap();
+            match byte {
+                b'$' => continue,
+                b'#' => break,
+                _ => response.push(byte),
+            }
+        }
+
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        stream.write_all(b"+").unwrap();
+
+        String::from_utf8(response).unwrap()
+    }
+
+    /// Serves a cpu on a local port, returning the connected client and the server thread,
+    /// which hands back the cpu once the session ends.
+    fn spawn<M: Mapper + Send + 'static>(
+        cpu: Cpu<M, NoInterrupts>,
+    ) -> (TcpStream, thread::JoinHandle<Cpu<M, NoInterrupts>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut cpu = cpu;
+            let (stream, _) = listener.accept().unwrap();
+            GdbStub::new(&mut cpu).serve(stream).unwrap();
+
+            cpu
+        });
+
+        (TcpStream::connect(address).unwrap(), server)
+    }
+
+    #[test]
+    fn test_session() {
+        // LDX #$03; loop: DEX; BNE loop; NOP
+        let memory = Memory::with_program(0x8000, &[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0xEA]);
+
+        let mut cpu = Cpu::new(memory, NoInterrupts);
+        cpu.initialize();
+        let (mut client, server) = spawn(cpu);
+
+        assert_eq!(request(&mut client, "?"), "S05");
+        assert_eq!(request(&mut client, "p4"), "0080");
+
+        assert_eq!(request(&mut client, "s"), "S05");
+        assert_eq!(request(&mut client, "p1"), "03");
+
+        assert_eq!(request(&mut client, "Z0,8005,1"), "OK");
+        assert_eq!(request(&mut client, "c"), "S05");
+        assert_eq!(request(&mut client, "g"), "000000fd058006");
+
+        assert_eq!(request(&mut client, "M0200,2:beef"), "OK");
+        assert_eq!(request(&mut client, "m01ff,3"), "00beef");
+        assert_eq!(request(&mut client, "m0000,ffffffff").len(), PACKET_SIZE);
+
+        assert_eq!(request(&mut client, "P0=42"), "OK");
+        assert_eq!(request(&mut client, "D"), "OK");
+
+        let cpu = server.join().unwrap();
+        assert_eq!(cpu.accumulator, 0x42);
+        assert_eq!(cpu.program_counter, 0x8005);
+    }
+
+    #[test]
+    fn test_step_65c02() {
+        // PHX; BRA +1; .byte $FF; STZ $10
+        let mut memory = Memory::with_program(0x8000, &[0xDA, 0x80, 0x01, 0xFF, 0x64, 0x10]);
+        memory.0[0x0010] = 0x42;
+
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_variant(Variant::Wdc65C02);
+        cpu.initialize();
+        cpu.x = 0x24;
+        let (mut client, server) = spawn(cpu);
+
+        assert_eq!(request(&mut client, "s"), "S05");
+        assert_eq!(request(&mut client, "m01fd,1"), "24");
+        assert_eq!(request(&mut client, "s"), "S05");
+        assert_eq!(request(&mut client, "p4"), "0480");
+        assert_eq!(request(&mut client, "s"), "S05");
+        assert_eq!(request(&mut client, "m0010,1"), "00");
+        assert_eq!(request(&mut client, "D"), "OK");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_kill() {
+        let mut cpu = Cpu::new(Memory([0; 0x10000]), NoInterrupts);
+        cpu.initialize();
+        let (mut client, server) = spawn(cpu);
+
+        let checksum = calculate_checksum(b"k");
+        write!(client, "$k#{:02x}", checksum).unwrap();
+        server.join().unwrap();
+
+        // only the packet is acknowledged
+        let mut bytes = Vec::new();
+        client.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"+");
+    }
+}
@@ -1,12 +1,14 @@
 use super::{
     absolute_read, absolute_x_read, absolute_y_read, handle_invalid_addressing_mode,
-    immediate_read, indirect_x_read, indirect_y_read, zeropage_read, zeropage_x_read,
+    immediate_read, indirect_x_read, indirect_y_read, zeropage_indirect_read, zeropage_read,
+    zeropage_x_read,
 };
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_adc(
         &mut self,
 
@@ -14,7 +16,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         low_byte: Option<u8>,
         high_byte: Option<u8>,
     ) -> u8 {
-        match addressing_mode {
+        let cycles = match addressing_mode {
             AddressingMode::Immediate => {
                 let value = immediate_read(low_byte);
                 self.adc_intermediate(value);
@@ -72,8 +74,17 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte);
+                self.adc_intermediate(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
-        }
+        };
+
+        // the 65C02 takes an extra cycle in decimal mode
+        cycles + (self.variant.is_cmos() && self.processor_status.decimal_flag()) as u8
     }
 
     pub(crate) fn instruction_sbc(
@@ -83,7 +94,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         low_byte: Option<u8>,
         high_byte: Option<u8>,
     ) -> u8 {
-        match addressing_mode {
+        let cycles = match addressing_mode {
             AddressingMode::Immediate => {
                 let value = immediate_read(low_byte);
                 self.sbc_intermediate(value);
@@ -141,8 +152,17 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte);
+                self.sbc_intermediate(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
-        }
+        };
+
+        // the 65C02 takes an extra cycle in decimal mode
+        cycles + (self.variant.is_cmos() && self.processor_status.decimal_flag()) as u8
     }
 
     pub(crate) fn instruction_cmp(
@@ -210,6 +230,12 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte);
+                self.cmp_intermediate(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -353,9 +379,16 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
     }
 }
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     /// The intermediate code for ADC. Modifies the accumulator inside this method.
     fn adc_intermediate(&mut self, value: u8) {
+        match self.variant.has_decimal_mode() && self.processor_status.decimal_flag() {
+            true => self.adc_decimal(value),
+            false => self.adc_binary(value),
+        }
+    }
+
+    fn adc_binary(&mut self, value: u8) {
         // If the sign bits are the same, then we need to check if they
         // are different later because that is an overflow.
         // If the sign bits are the same, we keep the sign in Some(), otherwise
@@ -399,9 +432,101 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
     /// The intermediate code for SBC. Modifies the accumulator inside this method.
     fn sbc_intermediate(&mut self, value: u8) {
-        // We can do a bit of twos comp math and simplify the operation to ADC(value ^ 0xFF).
-        // The forum post on this is here: https://forums.nesdev.org/viewtopic.php?t=8703
-        self.adc_intermediate(value ^ 0xFF);
+        match self.variant.has_decimal_mode() && self.processor_status.decimal_flag() {
+            true => self.sbc_decimal(value),
+            // We can do a bit of twos comp math and simplify the operation to ADC(value ^ 0xFF).
+            // The forum post on this is here: https://forums.nesdev.org/viewtopic.php?t=8703
+            false => self.adc_binary(value ^ 0xFF),
+        }
+    }
+
+    /// ADC in BCD. On the NMOS 6502, Z is set from the binary sum, while N and V are set
+    /// from the result before the high nibble is adjusted. The 65C02 sets N and Z from the
+    /// final result. The algorithm is from http://www.6502.org/tutorials/decimal_mode.html#A
+    fn adc_decimal(&mut self, value: u8) {
+        let accumulator = self.accumulator as u16;
+        let value = value as u16;
+        let carry = self.processor_status.carry_flag() as u16;
+
+        let mut low_nibble = (accumulator & 0x0F) + (value & 0x0F) + carry;
+        if low_nibble > 0x09 {
+            low_nibble += 0x06;
+        }
+
+        let mut result = (accumulator & 0xF0) + (value & 0xF0) + (low_nibble & 0x0F);
+        if low_nibble > 0x0F {
+            result += 0x10;
+        }
+
+        self.modify_zero_flag((accumulator + value + carry) as u8);
+        self.modify_negative_flag(result as u8);
+
+        // the signs of the inputs are the same, but differ from the sign of the result
+        match (accumulator ^ value) & 0x80 == 0 && (accumulator ^ result) & 0x80 != 0 {
+            true => self.processor_status.set_overflow_flag(),
+            false => self.processor_status.clear_overflow_flag(),
+        }
+
+        if result > 0x9F {
+            result += 0x60;
+        }
+
+        match result > 0xFF {
+            true => self.processor_status.set_carry_flag(),
+            false => self.processor_status.clear_carry_flag(),
+        }
+
+        self.accumulator = result as u8;
+
+        if self.variant.is_cmos() {
+            self.modify_zero_flag(self.accumulator);
+            self.modify_negative_flag(self.accumulator);
+        }
+    }
+
+    /// SBC in BCD. On the NMOS 6502 every flag is set the same way as a binary SBC, and
+    /// only the accumulator is adjusted.
+    fn sbc_decimal(&mut self, value: u8) {
+        if self.variant.is_cmos() {
+            return self.sbc_decimal_cmos(value);
+        }
+
+        let accumulator = self.accumulator as i16;
+        let borrow = !self.processor_status.carry_flag() as i16;
+
+        let mut low_nibble = (accumulator & 0x0F) - (value as i16 & 0x0F) - borrow;
+        let mut high_nibble = (accumulator >> 4) - (value as i16 >> 4);
+        if low_nibble < 0 {
+            low_nibble -= 0x06;
+            high_nibble -= 1;
+        }
+        if high_nibble < 0 {
+            high_nibble -= 0x06;
+        }
+
+        self.adc_binary(value ^ 0xFF);
+        self.accumulator = (((high_nibble & 0x0F) << 4) | (low_nibble & 0x0F)) as u8;
+    }
+
+    /// SBC in BCD as done by the 65C02, which adjusts the full difference instead of each
+    /// nibble. C and V are set as in a binary SBC, and N and Z from the result.
+    fn sbc_decimal_cmos(&mut self, value: u8) {
+        let accumulator = self.accumulator as i16;
+        let borrow = !self.processor_status.carry_flag() as i16;
+
+        let low_nibble = (accumulator & 0x0F) - (value as i16 & 0x0F) - borrow;
+        let mut result = accumulator - value as i16 - borrow;
+        if result < 0 {
+            result -= 0x60;
+        }
+        if low_nibble < 0 {
+            result -= 0x06;
+        }
+
+        self.adc_binary(value ^ 0xFF);
+        self.accumulator = result as u8;
+        self.modify_zero_flag(self.accumulator);
+        self.modify_negative_flag(self.accumulator);
     }
 
     fn cmp_intermediate(&mut self, value: u8) {
@@ -1,9 +1,10 @@
-use super::twos_compliment_to_signed;
 use super::Cpu;
+use super::{twos_compliment_to_signed, zeropage_read};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_bcc(&mut self, low_byte: Option<u8>) -> u8 {
         let needs_branch = !self.processor_status.carry_flag();
         branch(self, low_byte, needs_branch)
@@ -43,11 +44,31 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         let needs_branch = self.processor_status.overflow_flag();
         branch(self, low_byte, needs_branch)
     }
+
+    pub(crate) fn instruction_bra(&mut self, low_byte: Option<u8>) -> u8 {
+        branch(self, low_byte, true)
+    }
+
+    /// BBR and BBS, which branch by the offset in `high_byte` if the given bit of the
+    /// zeropage byte at `low_byte` is set (or clear, if `set` is false).
+    pub(crate) fn instruction_bbr_bbs(
+        &mut self,
+        bit: u8,
+        set: bool,
+        low_byte: Option<u8>,
+        high_byte: Option<u8>,
+    ) -> u8 {
+        let value = zeropage_read(self, low_byte);
+        let needs_branch = ((value >> bit) & 1 != 0) == set;
+
+        // reading the zeropage byte takes three more cycles than a regular branch
+        branch(self, high_byte, needs_branch) + 3
+    }
 }
 
 /// Executes a branch based on whether it needs a branch.
-fn branch<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn branch<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     needs_branch: bool,
 ) -> u8 {
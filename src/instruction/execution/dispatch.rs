@@ -0,0 +1,255 @@
+use super::system::InterruptState;
+use super::Cpu;
+use crate::instruction::{
+    AddressingMode, Instruction, Opcode, OpcodeInfo, OPCODE_TABLE, REV_A_OPCODE_TABLE,
+    WDC65C02_OPCODE_TABLE,
+};
+use crate::Interrupts;
+use crate::Mapper;
+use crate::Observer;
+
+/// Executes an instruction, returning the cycles taken.
+pub(crate) type Handler<M, I, O> = fn(&mut Cpu<M, I, O>, Instruction) -> u8;
+
+// Handlers of instructions with operands. The addressing mode of the opcode byte is
+// passed as a constant, so the mode match of the instruction folds into a handler per
+// mode.
+macro_rules! operand_handler {
+    (@modes $addressing_mode:expr, $method:ident, $arguments:tt, [$($mode:ident),*]) => {
+        match $addressing_mode {
+            $(AddressingMode::$mode => |cpu, instruction| {
+                operand_handler!(@call cpu, instruction, $method, $mode, $arguments)
+            },)*
+        }
+    };
+    (@call $cpu:ident, $instruction:ident, $method:ident, $mode:ident, [$($argument:expr),*]) => {
+        $cpu.$method(
+            AddressingMode::$mode,
+            $instruction.low_byte,
+            $instruction.high_byte,
+            $($argument),*
+        )
+    };
+    ($addressing_mode:expr, $method:ident $(, $argument:expr)*) => {
+        operand_handler!(
+            @modes $addressing_mode,
+            $method,
+            [$($argument),*],
+            [
+                Accumulator,
+                Absolute,
+                AbsoluteXIndexed,
+                AbsoluteYIndexed,
+                Immediate,
+                Implied,
+                Indirect,
+                IndirectXIndexed,
+                IndirectYIndexed,
+                Relative,
+                Zeropage,
+                ZeropageXIndexed,
+                ZeropageYIndexed,
+                ZeropageIndirect,
+                AbsoluteXIndexedIndirect,
+                ZeropageRelative
+            ]
+        )
+    };
+}
+
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
+    /// The handler of every opcode byte, with its addressing mode resolved. Illegal opcode
+    /// bytes are never executed and get NOP.
+    pub(crate) const HANDLERS: [Handler<M, I, O>; 256] = Self::handlers(&OPCODE_TABLE);
+
+    /// [`Self::HANDLERS`] for Rev. A chips.
+    pub(crate) const REV_A_HANDLERS: [Handler<M, I, O>; 256] = Self::handlers(&REV_A_OPCODE_TABLE);
+
+    /// [`Self::HANDLERS`] for the WDC 65C02.
+    pub(crate) const WDC65C02_HANDLERS: [Handler<M, I, O>; 256] =
+        Self::handlers(&WDC65C02_OPCODE_TABLE);
+
+    const fn handlers(table: &[Option<OpcodeInfo>; 256]) -> [Handler<M, I, O>; 256] {
+        let mut handlers = [Self::handler(Opcode::NOP, AddressingMode::Implied); 256];
+
+        let mut byte = 0;
+        while byte < 256 {
+            if let Some(info) = table[byte] {
+                handlers[byte] = Self::handler(info.opcode, info.addressing_mode);
+            }
+            byte += 1;
+        }
+
+        handlers
+    }
+
+    const fn handler(opcode: Opcode, addressing_mode: AddressingMode) -> Handler<M, I, O> {
+        match opcode {
+            Opcode::ADC => operand_handler!(addressing_mode, instruction_adc),
+            Opcode::AND => operand_handler!(addressing_mode, instruction_and),
+            Opcode::ASL => operand_handler!(addressing_mode, instruction_asl),
+            Opcode::BBR0 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(0, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR1 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(1, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR2 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(2, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR3 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(3, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR4 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(4, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR5 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(5, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR6 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(6, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBR7 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(7, false, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS0 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(0, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS1 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(1, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS2 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(2, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS3 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(3, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS4 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(4, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS5 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(5, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS6 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(6, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BBS7 => |cpu, instruction| {
+                cpu.instruction_bbr_bbs(7, true, instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::BCC => |cpu, instruction| cpu.instruction_bcc(instruction.low_byte),
+            Opcode::BCS => |cpu, instruction| cpu.instruction_bcs(instruction.low_byte),
+            Opcode::BEQ => |cpu, instruction| cpu.instruction_beq(instruction.low_byte),
+            Opcode::BIT => operand_handler!(addressing_mode, instruction_bit),
+            Opcode::BMI => |cpu, instruction| cpu.instruction_bmi(instruction.low_byte),
+            Opcode::BNE => |cpu, instruction| cpu.instruction_bne(instruction.low_byte),
+            Opcode::BPL => |cpu, instruction| cpu.instruction_bpl(instruction.low_byte),
+            Opcode::BRK => |cpu, _| cpu.instruction_brk(InterruptState::Inactive),
+            Opcode::BRA => |cpu, instruction| cpu.instruction_bra(instruction.low_byte),
+            Opcode::BVC => |cpu, instruction| cpu.instruction_bvc(instruction.low_byte),
+            Opcode::BVS => |cpu, instruction| cpu.instruction_bvs(instruction.low_byte),
+            Opcode::CLC => |cpu, _| cpu.instruction_clc(),
+            Opcode::CLD => |cpu, _| cpu.instruction_cld(),
+            Opcode::CLI => |cpu, _| cpu.instruction_cli(),
+            Opcode::CLV => |cpu, _| cpu.instruction_clv(),
+            Opcode::CMP => operand_handler!(addressing_mode, instruction_cmp),
+            Opcode::CPX => operand_handler!(addressing_mode, instruction_cpx),
+            Opcode::CPY => operand_handler!(addressing_mode, instruction_cpy),
+            Opcode::DEC => operand_handler!(addressing_mode, instruction_dec),
+            Opcode::DEX => |cpu, _| cpu.instruction_dex(),
+            Opcode::DEY => |cpu, _| cpu.instruction_dey(),
+            Opcode::EOR => operand_handler!(addressing_mode, instruction_eor),
+            Opcode::INC => operand_handler!(addressing_mode, instruction_inc),
+            Opcode::INX => |cpu, _| cpu.instruction_inx(),
+            Opcode::INY => |cpu, _| cpu.instruction_iny(),
+            Opcode::JMP => operand_handler!(addressing_mode, instruction_jmp),
+            Opcode::JSR => |cpu, instruction| {
+                cpu.instruction_jsr(instruction.low_byte, instruction.high_byte)
+            },
+            Opcode::LDA => operand_handler!(addressing_mode, instruction_lda),
+            Opcode::LDX => operand_handler!(addressing_mode, instruction_ldx),
+            Opcode::LDY => operand_handler!(addressing_mode, instruction_ldy),
+            Opcode::LSR => operand_handler!(addressing_mode, instruction_lsr),
+            Opcode::NOP => |cpu, _| cpu.instruction_nop(),
+            Opcode::ORA => operand_handler!(addressing_mode, instruction_ora),
+            Opcode::PHA => |cpu, _| cpu.instruction_pha(),
+            Opcode::PHP => |cpu, _| cpu.instruction_php(),
+            Opcode::PHX => |cpu, _| cpu.instruction_phx(),
+            Opcode::PHY => |cpu, _| cpu.instruction_phy(),
+            Opcode::PLA => |cpu, _| cpu.instruction_pla(),
+            Opcode::PLP => |cpu, _| cpu.instruction_plp(),
+            Opcode::PLX => |cpu, _| cpu.instruction_plx(),
+            Opcode::PLY => |cpu, _| cpu.instruction_ply(),
+            Opcode::RMB0 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(0, false, instruction.low_byte)
+            },
+            Opcode::RMB1 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(1, false, instruction.low_byte)
+            },
+            Opcode::RMB2 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(2, false, instruction.low_byte)
+            },
+            Opcode::RMB3 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(3, false, instruction.low_byte)
+            },
+            Opcode::RMB4 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(4, false, instruction.low_byte)
+            },
+            Opcode::RMB5 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(5, false, instruction.low_byte)
+            },
+            Opcode::RMB6 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(6, false, instruction.low_byte)
+            },
+            Opcode::RMB7 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(7, false, instruction.low_byte)
+            },
+            Opcode::ROL => operand_handler!(addressing_mode, instruction_rol),
+            Opcode::ROR => operand_handler!(addressing_mode, instruction_ror),
+            Opcode::RORRevA => operand_handler!(addressing_mode, instruction_ror_rev_a),
+            Opcode::RTI => |cpu, _| cpu.instruction_rti(),
+            Opcode::RTS => |cpu, _| cpu.instruction_rts(),
+            Opcode::SBC => operand_handler!(addressing_mode, instruction_sbc),
+            Opcode::SEC => |cpu, _| cpu.instruction_sec(),
+            Opcode::SED => |cpu, _| cpu.instruction_sed(),
+            Opcode::SEI => |cpu, _| cpu.instruction_sei(),
+            Opcode::SMB0 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(0, true, instruction.low_byte)
+            },
+            Opcode::SMB1 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(1, true, instruction.low_byte)
+            },
+            Opcode::SMB2 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(2, true, instruction.low_byte)
+            },
+            Opcode::SMB3 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(3, true, instruction.low_byte)
+            },
+            Opcode::SMB4 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(4, true, instruction.low_byte)
+            },
+            Opcode::SMB5 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(5, true, instruction.low_byte)
+            },
+            Opcode::SMB6 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(6, true, instruction.low_byte)
+            },
+            Opcode::SMB7 => |cpu, instruction| {
+                cpu.instruction_rmb_smb(7, true, instruction.low_byte)
+            },
+            Opcode::STA => operand_handler!(addressing_mode, instruction_sta),
+            Opcode::STX => operand_handler!(addressing_mode, instruction_stx),
+            Opcode::STY => operand_handler!(addressing_mode, instruction_sty),
+            Opcode::STP => |cpu, _| cpu.instruction_stp(),
+            Opcode::STZ => operand_handler!(addressing_mode, instruction_stz),
+            Opcode::TAX => |cpu, _| cpu.instruction_tax(),
+            Opcode::TAY => |cpu, _| cpu.instruction_tay(),
+            Opcode::TRB => operand_handler!(addressing_mode, instruction_tsb_trb, false),
+            Opcode::TSX => |cpu, _| cpu.instruction_tsx(),
+            Opcode::TSB => operand_handler!(addressing_mode, instruction_tsb_trb, true),
+            Opcode::TXA => |cpu, _| cpu.instruction_txa(),
+            Opcode::TXS => |cpu, _| cpu.instruction_txs(),
+            Opcode::TYA => |cpu, _| cpu.instruction_tya(),
+            Opcode::WAI => |cpu, _| cpu.instruction_wai(),
+        }
+    }
+}
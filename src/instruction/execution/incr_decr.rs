@@ -6,8 +6,9 @@ use super::{
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_inc(
         &mut self,
 
@@ -16,6 +17,14 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         high_byte: Option<u8>,
     ) -> u8 {
         match addressing_mode {
+            AddressingMode::Accumulator => {
+                self.accumulator = self.accumulator.wrapping_add(1);
+
+                self.modify_zero_flag(self.accumulator);
+                self.modify_negative_flag(self.accumulator);
+
+                2
+            }
             AddressingMode::Zeropage => {
                 let mut value = zeropage_read(self, low_byte);
 
@@ -94,6 +103,14 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         high_byte: Option<u8>,
     ) -> u8 {
         match addressing_mode {
+            AddressingMode::Accumulator => {
+                self.accumulator = self.accumulator.wrapping_sub(1);
+
+                self.modify_zero_flag(self.accumulator);
+                self.modify_negative_flag(self.accumulator);
+
+                2
+            }
             AddressingMode::Zeropage => {
                 let mut value = zeropage_read(self, low_byte);
 
@@ -1,9 +1,11 @@
 use super::{handle_invalid_addressing_mode, pack_bytes, pack_bytes_wrapped, unpack_bytes};
 use super::{AddressingMode, Cpu};
+use crate::call_stack::{CallFrame, FrameKind};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_jmp(
         &mut self,
 
@@ -22,16 +24,34 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 let base_address = pack_bytes_wrapped(low_byte, high_byte);
 
                 // check for the bug referenced here https://www.nesdev.org/obelisk-6502-guide/reference.html#JMP
-                self.program_counter = match (base_address & 0xFF) == 0xFF {
+                // the 65C02 fixed it at the cost of a cycle
+                let page_bug = (base_address & 0xFF) == 0xFF && !self.variant.is_cmos();
+                self.program_counter = match page_bug {
                     true => {
-                        let lsb = self.read(base_address);
-                        let msb = self.read(base_address - 0xFF);
+                        let lsb = self.observed_read(base_address);
+                        let msb = self.observed_read(base_address - 0xFF);
                         pack_bytes(lsb, msb)
                     }
-                    false => pack_bytes(self.read(base_address), self.read(base_address + 1)),
+                    false => pack_bytes(
+                        self.observed_read(base_address),
+                        self.observed_read(base_address.wrapping_add(1)),
+                    ),
                 };
 
-                5
+                match self.variant.is_cmos() {
+                    true => 6,
+                    false => 5,
+                }
+            }
+            AddressingMode::AbsoluteXIndexedIndirect => {
+                let base_address =
+                    pack_bytes_wrapped(low_byte, high_byte).wrapping_add(self.x as u16);
+                self.program_counter = pack_bytes(
+                    self.observed_read(base_address),
+                    self.observed_read(base_address.wrapping_add(1)),
+                );
+
+                6
             }
             _ => handle_invalid_addressing_mode(),
         }
@@ -39,6 +59,8 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
     pub(crate) fn instruction_jsr(&mut self, low_byte: Option<u8>, high_byte: Option<u8>) -> u8 {
         let subroutine_address = pack_bytes_wrapped(low_byte, high_byte);
+        let caller = self.program_counter.wrapping_sub(3);
+        let stack_pointer = self.stack_pointer;
 
         let (pc_low, pc_high) = unpack_bytes(self.program_counter - 1);
 
@@ -47,15 +69,33 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
         self.program_counter = subroutine_address;
 
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.push(
+                caller,
+                CallFrame {
+                    caller,
+                    target: subroutine_address,
+                    kind: FrameKind::Jsr,
+                    stack_pointer,
+                },
+            );
+        }
+
         6
     }
 
     pub(crate) fn instruction_rts(&mut self) -> u8 {
+        let address = self.program_counter.wrapping_sub(1);
+
         let pc_low = self.pop();
         let pc_high: u8 = self.pop();
 
         self.program_counter = pack_bytes(pc_low, pc_high) + 1;
 
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.pop(address, false, self.stack_pointer, self.program_counter);
+        }
+
         6
     }
 }
@@ -2,8 +2,9 @@ use super::*;
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_lda(
         &mut self,
 
@@ -93,6 +94,15 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte);
+
+                self.accumulator = value;
+                self.modify_negative_flag(value);
+                self.modify_zero_flag(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -259,6 +269,10 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 indirect_y_write(self, low_byte, self.accumulator);
                 6
             }
+            AddressingMode::ZeropageIndirect => {
+                zeropage_indirect_write(self, low_byte, self.accumulator);
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -318,4 +332,32 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
             _ => handle_invalid_addressing_mode(),
         }
     }
+
+    pub(crate) fn instruction_stz(
+        &mut self,
+
+        addressing_mode: AddressingMode,
+        low_byte: Option<u8>,
+        high_byte: Option<u8>,
+    ) -> u8 {
+        match addressing_mode {
+            AddressingMode::Zeropage => {
+                zeropage_write(self, low_byte, 0);
+                3
+            }
+            AddressingMode::ZeropageXIndexed => {
+                zeropage_x_write(self, low_byte, 0);
+                4
+            }
+            AddressingMode::Absolute => {
+                absolute_write(self, low_byte, high_byte, 0);
+                4
+            }
+            AddressingMode::AbsoluteXIndexed => {
+                absolute_x_write(self, low_byte, high_byte, 0);
+                5
+            }
+            _ => handle_invalid_addressing_mode(),
+        }
+    }
 }
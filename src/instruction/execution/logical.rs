@@ -1,12 +1,14 @@
 use super::{
-    absolute_read, absolute_x_read, absolute_y_read, handle_invalid_addressing_mode,
-    immediate_read, indirect_x_read, indirect_y_read, zeropage_read, zeropage_x_read,
+    absolute_read, absolute_write, absolute_x_read, absolute_y_read,
+    handle_invalid_addressing_mode, immediate_read, indirect_x_read, indirect_y_read,
+    zeropage_indirect_read, zeropage_read, zeropage_write, zeropage_x_read,
 };
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_and(
         &mut self,
 
@@ -91,6 +93,15 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte) & self.accumulator;
+
+                self.accumulator = value;
+                self.modify_negative_flag(value);
+                self.modify_zero_flag(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -179,6 +190,15 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte) ^ self.accumulator;
+
+                self.accumulator = value;
+                self.modify_negative_flag(value);
+                self.modify_zero_flag(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -267,6 +287,15 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                     false => 5,
                 }
             }
+            AddressingMode::ZeropageIndirect => {
+                let value = zeropage_indirect_read(self, low_byte) | self.accumulator;
+
+                self.accumulator = value;
+                self.modify_negative_flag(value);
+                self.modify_zero_flag(value);
+
+                5
+            }
             _ => handle_invalid_addressing_mode(),
         }
     }
@@ -279,45 +308,104 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         high_byte: Option<u8>,
     ) -> u8 {
         match addressing_mode {
+            AddressingMode::Immediate => {
+                // the immediate form only sets the zero flag
+                self.modify_zero_flag(immediate_read(low_byte) & self.accumulator);
+
+                2
+            }
             AddressingMode::Zeropage => {
                 let raw = zeropage_read(self, low_byte);
-
-                // check bit 7
-                match (raw & 0b1000_0000) != 0 {
-                    true => self.processor_status.set_negative_flag(),
-                    false => self.processor_status.clear_negative_flag(),
-                };
-
-                // check bit 6
-                match (raw & 0b0100_0000) != 0 {
-                    true => self.processor_status.set_overflow_flag(),
-                    false => self.processor_status.clear_overflow_flag(),
-                };
-
-                self.modify_zero_flag(raw & self.accumulator);
+                self.bit_intermediate(raw);
 
                 3
             }
+            AddressingMode::ZeropageXIndexed => {
+                let raw = zeropage_x_read(self, low_byte);
+                self.bit_intermediate(raw);
+
+                4
+            }
             AddressingMode::Absolute => {
                 let raw = absolute_read(self, low_byte, high_byte);
+                self.bit_intermediate(raw);
 
-                // check bit 7
-                match (raw & 0b1000_0000) != 0 {
-                    true => self.processor_status.set_negative_flag(),
-                    false => self.processor_status.clear_negative_flag(),
-                };
+                4
+            }
+            AddressingMode::AbsoluteXIndexed => {
+                let (raw, page_changed) = absolute_x_read(self, low_byte, high_byte);
+                self.bit_intermediate(raw);
 
-                // check bit 6
-                match (raw & 0b0100_0000) != 0 {
-                    true => self.processor_status.set_overflow_flag(),
-                    false => self.processor_status.clear_overflow_flag(),
-                };
+                match page_changed {
+                    true => 5,
+                    false => 4,
+                }
+            }
+            _ => handle_invalid_addressing_mode(),
+        }
+    }
 
-                self.modify_zero_flag(raw & self.accumulator);
+    /// Sets bits in memory from the accumulator (TSB), or clears them (TRB) when `set` is
+    /// false. The zero flag is set from the bits the accumulator and memory had in common.
+    pub(crate) fn instruction_tsb_trb(
+        &mut self,
 
-                4
+        addressing_mode: AddressingMode,
+        low_byte: Option<u8>,
+        high_byte: Option<u8>,
+        set: bool,
+    ) -> u8 {
+        let modify = |value: u8, accumulator: u8| match set {
+            true => value | accumulator,
+            false => value & !accumulator,
+        };
+
+        match addressing_mode {
+            AddressingMode::Zeropage => {
+                let value = zeropage_read(self, low_byte);
+                self.modify_zero_flag(value & self.accumulator);
+                zeropage_write(self, low_byte, modify(value, self.accumulator));
+
+                5
+            }
+            AddressingMode::Absolute => {
+                let value = absolute_read(self, low_byte, high_byte);
+                self.modify_zero_flag(value & self.accumulator);
+                absolute_write(self, low_byte, high_byte, modify(value, self.accumulator));
+
+                6
             }
             _ => handle_invalid_addressing_mode(),
         }
     }
+
+    /// RMB and SMB, which clear or set a single bit of a zeropage byte.
+    pub(crate) fn instruction_rmb_smb(&mut self, bit: u8, set: bool, low_byte: Option<u8>) -> u8 {
+        let value = zeropage_read(self, low_byte);
+        let value = match set {
+            true => value | (1 << bit),
+            false => value & !(1 << bit),
+        };
+        zeropage_write(self, low_byte, value);
+
+        5
+    }
+}
+
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
+    fn bit_intermediate(&mut self, raw: u8) {
+        // check bit 7
+        match (raw & 0b1000_0000) != 0 {
+            true => self.processor_status.set_negative_flag(),
+            false => self.processor_status.clear_negative_flag(),
+        };
+
+        // check bit 6
+        match (raw & 0b0100_0000) != 0 {
+            true => self.processor_status.set_overflow_flag(),
+            false => self.processor_status.clear_overflow_flag(),
+        };
+
+        self.modify_zero_flag(raw & self.accumulator);
+    }
 }
@@ -1,11 +1,13 @@
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
 // We organize the instructions using modules according to the
 // categories used on https://www.nesdev.org/obelisk-6502-guide/instructions.html
 mod arithmetic;
 mod branches;
+mod dispatch;
 mod incr_decr;
 mod jumps_calls;
 mod load_store;
@@ -16,7 +18,7 @@ mod stack;
 mod status_flags;
 pub(crate) mod system;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     /// Sets the zero flag if the given byte is 0.
     fn modify_zero_flag(&mut self, byte: u8) {
         match byte == 0 {
@@ -44,7 +46,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
     fn pop(&mut self) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
 
-        self.read(0x0100 | self.stack_pointer as u16)
+        self.observed_read(0x0100 | self.stack_pointer as u16)
     }
 }
 
@@ -84,24 +86,34 @@ fn immediate_read(low_byte: Option<u8>) -> u8 {
     low_byte.unwrap()
 }
 
-fn zeropage_read<M: Mapper, I: Interrupts>(cpu: &Cpu<M, I>, low_byte: Option<u8>) -> u8 {
+fn zeropage_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> u8 {
     let address = low_byte.unwrap() as u16;
-    cpu.read(address)
+    cpu.observed_read(address)
 }
 
 // value is the value written to memory
-fn zeropage_write<M: Mapper, I: Interrupts>(cpu: &mut Cpu<M, I>, low_byte: Option<u8>, value: u8) {
+fn zeropage_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+    value: u8,
+) {
     let address = low_byte.unwrap() as u16;
     cpu.write(address, value);
 }
 
-fn zeropage_x_read<M: Mapper, I: Interrupts>(cpu: &Cpu<M, I>, low_byte: Option<u8>) -> u8 {
+fn zeropage_x_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> u8 {
     let address = low_byte.unwrap().wrapping_add(cpu.x) as u16;
-    cpu.read(address)
+    cpu.observed_read(address)
 }
 
-fn zeropage_x_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn zeropage_x_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     value: u8,
 ) {
@@ -109,13 +121,16 @@ fn zeropage_x_write<M: Mapper, I: Interrupts>(
     cpu.write(address, value);
 }
 
-fn zeropage_y_read<M: Mapper, I: Interrupts>(cpu: &Cpu<M, I>, low_byte: Option<u8>) -> u8 {
+fn zeropage_y_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> u8 {
     let address = low_byte.unwrap().wrapping_add(cpu.y) as u16;
-    cpu.read(address)
+    cpu.observed_read(address)
 }
 
-fn zeropage_y_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn zeropage_y_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     value: u8,
 ) {
@@ -123,17 +138,17 @@ fn zeropage_y_write<M: Mapper, I: Interrupts>(
     cpu.write(address, value);
 }
 
-fn absolute_read<M: Mapper, I: Interrupts>(
-    cpu: &Cpu<M, I>,
+fn absolute_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
 ) -> u8 {
     let address = pack_bytes_wrapped(low_byte, high_byte);
-    cpu.read(address)
+    cpu.observed_read(address)
 }
 
-fn absolute_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn absolute_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
     value: u8,
@@ -143,8 +158,8 @@ fn absolute_write<M: Mapper, I: Interrupts>(
 }
 
 /// Returns the value and whether a page boundary was crossed.
-fn absolute_x_read<M: Mapper, I: Interrupts>(
-    cpu: &Cpu<M, I>,
+fn absolute_x_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
 ) -> (u8, bool) {
@@ -153,11 +168,11 @@ fn absolute_x_read<M: Mapper, I: Interrupts>(
 
     let page_changed = low_byte.unwrap().checked_add(cpu.x).is_none();
 
-    (cpu.read(address), page_changed)
+    (cpu.observed_read(address), page_changed)
 }
 
-fn absolute_x_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn absolute_x_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
     value: u8,
@@ -168,8 +183,8 @@ fn absolute_x_write<M: Mapper, I: Interrupts>(
 }
 
 /// Returns the value and whether a page boundary was crossed.
-fn absolute_y_read<M: Mapper, I: Interrupts>(
-    cpu: &Cpu<M, I>,
+fn absolute_y_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
 ) -> (u8, bool) {
@@ -178,11 +193,11 @@ fn absolute_y_read<M: Mapper, I: Interrupts>(
 
     let page_changed = low_byte.unwrap().checked_add(cpu.y).is_none();
 
-    (cpu.read(address), page_changed)
+    (cpu.observed_read(address), page_changed)
 }
 
-fn absolute_y_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn absolute_y_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     high_byte: Option<u8>,
     value: u8,
@@ -192,50 +207,82 @@ fn absolute_y_write<M: Mapper, I: Interrupts>(
     cpu.write(address, value);
 }
 
-fn indirect_x_read<M: Mapper, I: Interrupts>(cpu: &Cpu<M, I>, low_byte: Option<u8>) -> u8 {
-    let address_low_byte = cpu.read(low_byte.unwrap().wrapping_add(cpu.x) as u16);
-    let address_high_byte = cpu.read(low_byte.unwrap().wrapping_add(cpu.x).wrapping_add(1) as u16);
+fn indirect_x_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> u8 {
+    let address_low_byte = cpu.observed_read(low_byte.unwrap().wrapping_add(cpu.x) as u16);
+    let address_high_byte = cpu.observed_read(low_byte.unwrap().wrapping_add(cpu.x).wrapping_add(1) as u16);
 
     let address = ((address_high_byte as u16) << 8) | (address_low_byte as u16);
 
-    cpu.read(address)
+    cpu.observed_read(address)
 }
 
-fn indirect_x_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn indirect_x_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     value: u8,
 ) {
     let lsb_base_address = low_byte.unwrap().wrapping_add(cpu.x) as u16;
     let msb_base_address = low_byte.unwrap().wrapping_add(cpu.x).wrapping_add(1) as u16;
 
-    let resolved_address = pack_bytes(cpu.read(lsb_base_address), cpu.read(msb_base_address));
+    let resolved_address = pack_bytes(cpu.observed_read(lsb_base_address), cpu.observed_read(msb_base_address));
 
     cpu.write(resolved_address, value);
 }
 
-fn indirect_y_read<M: Mapper, I: Interrupts>(cpu: &Cpu<M, I>, low_byte: Option<u8>) -> (u8, bool) {
+fn indirect_y_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> (u8, bool) {
     let low_base_address = low_byte.unwrap() as u16;
     let high_base_address = low_byte.unwrap().wrapping_add(1) as u16;
 
-    let page_changed = low_base_address > high_base_address;
+    let base_address = pack_bytes(cpu.observed_read(low_base_address), cpu.observed_read(high_base_address));
+    let resolved_address = base_address.wrapping_add(cpu.y as u16);
 
-    let resolved_address = pack_bytes(cpu.read(low_base_address), cpu.read(high_base_address))
-        .wrapping_add(cpu.y as u16);
+    // adding y to the base address carried into the high byte
+    let page_changed = (base_address & 0xFF00) != (resolved_address & 0xFF00);
 
-    (cpu.read(resolved_address), page_changed)
+    (cpu.observed_read(resolved_address), page_changed)
 }
 
-fn indirect_y_write<M: Mapper, I: Interrupts>(
-    cpu: &mut Cpu<M, I>,
+fn indirect_y_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
     low_byte: Option<u8>,
     value: u8,
 ) {
     let low_base_address = low_byte.unwrap() as u16;
     let high_base_address = low_byte.unwrap().wrapping_add(1) as u16;
 
-    let resolved_address = pack_bytes(cpu.read(low_base_address), cpu.read(high_base_address))
+    let resolved_address = pack_bytes(cpu.observed_read(low_base_address), cpu.observed_read(high_base_address))
         .wrapping_add(cpu.y as u16);
 
     cpu.write(resolved_address, value);
 }
+
+fn zeropage_indirect_read<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+) -> u8 {
+    let low_base_address = low_byte.unwrap() as u16;
+    let high_base_address = low_byte.unwrap().wrapping_add(1) as u16;
+
+    let resolved_address = pack_bytes(cpu.observed_read(low_base_address), cpu.observed_read(high_base_address));
+
+    cpu.observed_read(resolved_address)
+}
+
+fn zeropage_indirect_write<M: Mapper, I: Interrupts, O: Observer>(
+    cpu: &mut Cpu<M, I, O>,
+    low_byte: Option<u8>,
+    value: u8,
+) {
+    let low_base_address = low_byte.unwrap() as u16;
+    let high_base_address = low_byte.unwrap().wrapping_add(1) as u16;
+
+    let resolved_address = pack_bytes(cpu.observed_read(low_base_address), cpu.observed_read(high_base_address));
+
+    cpu.write(resolved_address, value);
+}
@@ -6,8 +6,9 @@ use super::{
 use super::{AddressingMode, Cpu};
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_asl(
         &mut self,
 
@@ -81,7 +82,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 6
             }
             AddressingMode::AbsoluteXIndexed => {
-                let (mut value, _) = absolute_x_read(self, low_byte, high_byte);
+                let (mut value, page_changed) = absolute_x_read(self, low_byte, high_byte);
 
                 match (value & 0b1000_0000) != 0 {
                     true => self.processor_status.set_carry_flag(),
@@ -95,7 +96,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
                 absolute_x_write(self, low_byte, high_byte, value);
 
-                7
+                self.shift_absolute_x_cycles(page_changed)
             }
             _ => handle_invalid_addressing_mode(),
         }
@@ -173,7 +174,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 6
             }
             AddressingMode::AbsoluteXIndexed => {
-                let (mut value, _) = absolute_x_read(self, low_byte, high_byte);
+                let (mut value, page_changed) = absolute_x_read(self, low_byte, high_byte);
 
                 match (value & 0b0000_0001) != 0 {
                     true => self.processor_status.set_carry_flag(),
@@ -187,7 +188,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
                 absolute_x_write(self, low_byte, high_byte, value);
 
-                7
+                self.shift_absolute_x_cycles(page_changed)
             }
             _ => handle_invalid_addressing_mode(),
         }
@@ -278,7 +279,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 6
             }
             AddressingMode::AbsoluteXIndexed => {
-                let (mut value, _) = absolute_x_read(self, low_byte, high_byte);
+                let (mut value, page_changed) = absolute_x_read(self, low_byte, high_byte);
 
                 let old_carry_flag = self.processor_status.carry_flag();
 
@@ -295,12 +296,30 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
                 absolute_x_write(self, low_byte, high_byte, value);
 
-                7
+                self.shift_absolute_x_cycles(page_changed)
             }
             _ => handle_invalid_addressing_mode(),
         }
     }
 
+    /// ROR of Rev. A chips, which shifts left instead and keeps the carry flag.
+    pub(crate) fn instruction_ror_rev_a(
+        &mut self,
+        addressing_mode: AddressingMode,
+        low_byte: Option<u8>,
+        high_byte: Option<u8>,
+    ) -> u8 {
+        let carry_flag = self.processor_status.carry_flag();
+        let cycles = self.instruction_asl(addressing_mode, low_byte, high_byte);
+
+        match carry_flag {
+            true => self.processor_status.set_carry_flag(),
+            false => self.processor_status.clear_carry_flag(),
+        }
+
+        cycles
+    }
+
     pub(crate) fn instruction_ror(
         &mut self,
 
@@ -386,7 +405,7 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
                 6
             }
             AddressingMode::AbsoluteXIndexed => {
-                let (mut value, _) = absolute_x_read(self, low_byte, high_byte);
+                let (mut value, page_changed) = absolute_x_read(self, low_byte, high_byte);
 
                 let old_carry_flag = self.processor_status.carry_flag();
 
@@ -403,9 +422,51 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
                 absolute_x_write(self, low_byte, high_byte, value);
 
-                7
+                self.shift_absolute_x_cycles(page_changed)
             }
             _ => handle_invalid_addressing_mode(),
         }
     }
 }
+
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
+    /// The 65C02 only takes the extra cycle of an absolute,X shift when a page is crossed.
+    fn shift_absolute_x_cycles(&self, page_changed: bool) -> u8 {
+        match self.variant.is_cmos() {
+            true => 6 + page_changed as u8,
+            false => 7,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{Memory, NoInterrupts};
+    use crate::{Cpu, Revision};
+
+    #[test]
+    fn test_rev_a_ror() {
+        let mut memory = Memory([0; 0x10000]);
+        memory.0[0x0200] = 0x6A; // ROR A
+        memory.0[0x0201] = 0x66; // ROR $10
+        memory.0[0x0202] = 0x10;
+        memory.0[0x10] = 0x40;
+
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_revision(Revision::RevA);
+        cpu.initialized = true;
+        cpu.program_counter = 0x0200;
+        cpu.accumulator = 0x81;
+        cpu.processor_status.set_carry_flag();
+
+        // shifts left, leaving the carry set
+        assert_eq!(cpu.cycle(), 2);
+        assert_eq!(cpu.accumulator, 0x02);
+        assert!(cpu.processor_status.carry_flag());
+
+        cpu.processor_status.clear_carry_flag();
+        assert_eq!(cpu.cycle(), 5);
+        assert_eq!(cpu.memory_mapper.0[0x10], 0x80);
+        assert!(!cpu.processor_status.carry_flag());
+        assert!(cpu.processor_status.negative_flag());
+    }
+}
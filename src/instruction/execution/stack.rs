@@ -1,9 +1,10 @@
 use super::Cpu;
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 use crate::ProcessorStatus;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_tsx(&mut self) -> u8 {
         self.x = self.stack_pointer;
 
@@ -46,4 +47,30 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
         4
     }
+
+    pub(crate) fn instruction_phx(&mut self) -> u8 {
+        self.push(self.x);
+        3
+    }
+
+    pub(crate) fn instruction_phy(&mut self) -> u8 {
+        self.push(self.y);
+        3
+    }
+
+    pub(crate) fn instruction_plx(&mut self) -> u8 {
+        self.x = self.pop();
+        self.modify_zero_flag(self.x);
+        self.modify_negative_flag(self.x);
+
+        4
+    }
+
+    pub(crate) fn instruction_ply(&mut self) -> u8 {
+        self.y = self.pop();
+        self.modify_zero_flag(self.y);
+        self.modify_negative_flag(self.y);
+
+        4
+    }
 }
@@ -1,8 +1,9 @@
 use super::Cpu;
 use crate::Interrupts;
 use crate::Mapper;
+use crate::Observer;
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     pub(crate) fn instruction_clc(&mut self) -> u8 {
         self.processor_status.clear_carry_flag();
         2
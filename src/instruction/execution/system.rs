@@ -1,9 +1,11 @@
 use super::Cpu;
 use super::{pack_bytes, unpack_bytes};
+use crate::call_stack::{CallFrame, FrameKind};
+use crate::observer::InterruptKind;
 use crate::processor_status::ProcessorStatus;
 use crate::IRQ_BRK_VECTOR_ADDRESS;
 use crate::{Interrupts, RESET_VECTOR_ADDRESS};
-use crate::{Mapper, NMI_VECTOR_ADDRESS};
+use crate::{Mapper, Observer, NMI_VECTOR_ADDRESS};
 
 /// Describes the interrupt state that triggered a BRK to determine which reset vector to use.
 /// Also includes Reset, on top of the normal interrupts.
@@ -15,9 +17,11 @@ pub(crate) enum InterruptState {
     NonMaskableInterrupt,
 }
 
-impl<M: Mapper, I: Interrupts> Cpu<M, I> {
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
     // more information on BRK https://www.nesdev.org/wiki/Visual6502wiki/6502_BRK_and_B_bit
     pub(crate) fn instruction_brk(&mut self, interrupt_state: InterruptState) -> u8 {
+        let stack_pointer = self.stack_pointer;
+
         // we skip ahead 1 byte because the byte after a BRK provides debugging information
         let (pc_low, pc_high) = unpack_bytes(self.program_counter);
 
@@ -36,24 +40,61 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
             self.processor_status.clear_break_flag();
         }
 
+        match interrupt_state {
+            InterruptState::Inactive => {}
+            InterruptState::Reset => self.observer.on_interrupt(InterruptKind::Reset),
+            InterruptState::MaskableInterrupt => self.observer.on_interrupt(InterruptKind::Irq),
+            InterruptState::NonMaskableInterrupt => self.observer.on_interrupt(InterruptKind::Nmi),
+        }
+
         // interrupt disable is set after pushing flags to stack https://www.nesdev.org/wiki/Status_flags#I:_Interrupt_Disable
         self.processor_status.set_interrupt_disable_flag();
 
+        // the 65C02 also leaves decimal mode
+        if self.variant.is_cmos() {
+            self.processor_status.clear_decimal_flag();
+        }
+
         self.program_counter = match interrupt_state {
             InterruptState::Inactive | InterruptState::MaskableInterrupt => pack_bytes(
-                self.read(IRQ_BRK_VECTOR_ADDRESS),
-                self.read(IRQ_BRK_VECTOR_ADDRESS + 1),
+                self.observed_read(IRQ_BRK_VECTOR_ADDRESS),
+                self.observed_read(IRQ_BRK_VECTOR_ADDRESS + 1),
             ),
             InterruptState::Reset => pack_bytes(
-                self.read(RESET_VECTOR_ADDRESS),
-                self.read(RESET_VECTOR_ADDRESS + 1),
+                self.observed_read(RESET_VECTOR_ADDRESS),
+                self.observed_read(RESET_VECTOR_ADDRESS + 1),
             ),
             InterruptState::NonMaskableInterrupt => pack_bytes(
-                self.read(NMI_VECTOR_ADDRESS),
-                self.read(NMI_VECTOR_ADDRESS + 1),
+                self.observed_read(NMI_VECTOR_ADDRESS),
+                self.observed_read(NMI_VECTOR_ADDRESS + 1),
             ),
         };
 
+        if let Some(call_stack) = &mut self.call_stack {
+            let pushed_pc = pack_bytes(pc_low, pc_high);
+
+            // the pushed program counter of a BRK already points past its debugging byte
+            let caller_and_kind = match interrupt_state {
+                InterruptState::Inactive => Some((pushed_pc.wrapping_sub(2), FrameKind::Brk)),
+                InterruptState::MaskableInterrupt => Some((pushed_pc, FrameKind::Irq)),
+                InterruptState::NonMaskableInterrupt => Some((pushed_pc, FrameKind::Nmi)),
+                InterruptState::Reset => None,
+            };
+
+            match caller_and_kind {
+                Some((caller, kind)) => call_stack.push(
+                    caller,
+                    CallFrame {
+                        caller,
+                        target: self.program_counter,
+                        kind,
+                        stack_pointer,
+                    },
+                ),
+                None => call_stack.clear(),
+            }
+        }
+
         7
     }
 
@@ -61,7 +102,19 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         2
     }
 
+    pub(crate) fn instruction_wai(&mut self) -> u8 {
+        self.waiting = true;
+        3
+    }
+
+    pub(crate) fn instruction_stp(&mut self) -> u8 {
+        self.stopped = true;
+        3
+    }
+
     pub(crate) fn instruction_rti(&mut self) -> u8 {
+        let address = self.program_counter.wrapping_sub(1);
+
         // ignore the new break flag and bit 5
         self.processor_status =
             ProcessorStatus((self.pop() & 0b1100_1111) | (self.processor_status.0 & 0b0011_0000));
@@ -71,6 +124,10 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
 
         self.program_counter = pack_bytes(pc_low, pc_high);
 
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.pop(address, true, self.stack_pointer, self.program_counter);
+        }
+
         6
     }
 }
@@ -2,13 +2,20 @@
 #![allow(clippy::upper_case_acronyms)]
 
 use super::Cpu;
+use crate::{Revision, Variant};
+use core::fmt;
 
 pub(crate) mod execution;
+mod table;
+mod wdc65c02;
+
+pub use table::{OpcodeInfo, OPCODE_TABLE, REV_A_OPCODE_TABLE, WDC65C02_OPCODE_TABLE};
 
 // https://emudev.de/nes-emulator/opcodes-and-addressing-modes-the-6502/   <-- good stuff
 // https://blogs.oregonstate.edu/ericmorgan/2022/01/21/6502-addressing-modes/  <--- also this too
 // https://www.masswerk.at/6502/6502_instruction_set.html#LDY <-- and here!
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AddressingMode {
     Accumulator,
     Absolute,
@@ -24,6 +31,13 @@ pub enum AddressingMode {
     Zeropage,
     ZeropageXIndexed,
     ZeropageYIndexed,
+    /// `($10)`, only on the 65C02.
+    ZeropageIndirect,
+    /// `($1234,X)`, only used by JMP on the 65C02.
+    AbsoluteXIndexedIndirect,
+    /// `$10,$20`, a zeropage address followed by a branch offset. Only used by BBR and BBS
+    /// on the 65C02.
+    ZeropageRelative,
 }
 
 impl AddressingMode {
@@ -39,21 +53,41 @@ impl AddressingMode {
             | AddressingMode::Relative
             | AddressingMode::Zeropage
             | AddressingMode::ZeropageXIndexed
-            | AddressingMode::ZeropageYIndexed => 2,
+            | AddressingMode::ZeropageYIndexed
+            | AddressingMode::ZeropageIndirect => 2,
             //
             AddressingMode::Absolute
             | AddressingMode::AbsoluteXIndexed
             | AddressingMode::AbsoluteYIndexed
-            | AddressingMode::Indirect => 3,
+            | AddressingMode::Indirect
+            | AddressingMode::AbsoluteXIndexedIndirect
+            | AddressingMode::ZeropageRelative => 3,
         }
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Opcode {
     ADC,
     AND,
     ASL,
+    BBR0,
+    BBR1,
+    BBR2,
+    BBR3,
+    BBR4,
+    BBR5,
+    BBR6,
+    BBR7,
+    BBS0,
+    BBS1,
+    BBS2,
+    BBS3,
+    BBS4,
+    BBS5,
+    BBS6,
+    BBS7,
     BCC,
     BCS,
     BEQ,
@@ -61,6 +95,7 @@ pub enum Opcode {
     BMI,
     BNE,
     BPL,
+    BRA,
     BRK,
     BVC,
     BVS,
@@ -89,25 +124,53 @@ pub enum Opcode {
     ORA,
     PHA,
     PHP,
+    PHX,
+    PHY,
     PLA,
     PLP,
+    PLX,
+    PLY,
+    RMB0,
+    RMB1,
+    RMB2,
+    RMB3,
+    RMB4,
+    RMB5,
+    RMB6,
+    RMB7,
     ROL,
     ROR,
+    /// ROR on Rev. A chips, which shifts left like ASL without changing the carry flag. It
+    /// is disassembled as ROR. See [`Revision::RevA`].
+    RORRevA,
     RTI,
     RTS,
     SBC,
     SEC,
     SED,
     SEI,
+    SMB0,
+    SMB1,
+    SMB2,
+    SMB3,
+    SMB4,
+    SMB5,
+    SMB6,
+    SMB7,
     STA,
+    STP,
     STX,
     STY,
+    STZ,
     TAX,
     TAY,
+    TRB,
+    TSB,
     TSX,
     TXA,
     TXS,
     TYA,
+    WAI,
 }
 
 /// Includes both the opcode and the addressing mode from
@@ -119,6 +182,7 @@ pub struct FullOpcode {
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction {
     pub opcode: Opcode,
     pub addressing_mode: AddressingMode,
@@ -126,35 +190,165 @@ pub struct Instruction {
     pub high_byte: Option<u8>,
 }
 
+impl Instruction {
+    /// Decodes the instruction starting at `address`, using `read` to get each byte.
+    /// Returns None if the opcode is illegal.
+    pub fn decode(address: u16, read: impl FnMut(u16) -> u8) -> Option<Instruction> {
+        Self::decode_for(address, Variant::default(), Revision::default(), read)
+    }
+
+    /// Decodes the instruction starting at `address` as `variant` at `revision` would. See
+    /// [`Self::decode`].
+    pub fn decode_for(
+        address: u16,
+        variant: Variant,
+        revision: Revision,
+        mut read: impl FnMut(u16) -> u8,
+    ) -> Option<Instruction> {
+        let info = variant.opcode_table(revision)[read(address) as usize]?;
+        Some(Self::with_operands(address, info, read))
+    }
+
+    /// Builds the instruction of an opcode byte that was already read from `address`,
+    /// reading its operands with `read`.
+    pub(crate) fn with_operands(
+        address: u16,
+        info: OpcodeInfo,
+        mut read: impl FnMut(u16) -> u8,
+    ) -> Instruction {
+        let mut instruction = Instruction {
+            opcode: info.opcode,
+            addressing_mode: info.addressing_mode,
+            low_byte: None,
+            high_byte: None,
+        };
+
+        // Low byte comes first as words are in little-endian
+        match instruction.size() {
+            1 => {}
+            2 => instruction.low_byte = Some(read(address.wrapping_add(1))),
+            3 => {
+                instruction.low_byte = Some(read(address.wrapping_add(1)));
+                instruction.high_byte = Some(read(address.wrapping_add(2)));
+            }
+            _ => unreachable!(),
+        };
+
+        instruction
+    }
+
+    /// The size of the instruction in bytes, including the opcode byte.
+    pub fn size(&self) -> u16 {
+        // BRK has 1 byte of debugging information right after it, giving
+        // it a size of 2.
+        match self.opcode {
+            Opcode::BRK => 2,
+            _ => self.addressing_mode.bytes_required(),
+        }
+    }
+
+    /// Returns the address a branch would jump to if it was located at `address`.
+    /// Returns None if this is not a branch.
+    pub fn branch_target(&self, address: u16) -> Option<u16> {
+        let offset = match self.addressing_mode {
+            AddressingMode::Relative => self.low_byte?,
+            AddressingMode::ZeropageRelative => self.high_byte?,
+            _ => return None,
+        };
+
+        Some(
+            address
+                .wrapping_add(self.size())
+                .wrapping_add(offset as i8 as u16),
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction in standard assembly syntax, such as `LDA ($10),Y`.
+    /// Branches show their raw offset, see [`Instruction::branch_target`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let low = self.low_byte.unwrap_or_default();
+        let word = ((self.high_byte.unwrap_or_default() as u16) << 8) | low as u16;
+
+        // LDX and STX decode zeropage,y as zeropage,x and patch it during execution
+        let addressing_mode = match (self.opcode, self.addressing_mode) {
+            (Opcode::LDX | Opcode::STX, AddressingMode::ZeropageXIndexed) => {
+                AddressingMode::ZeropageYIndexed
+            }
+            (_, addressing_mode) => addressing_mode,
+        };
+
+        match self.opcode {
+            Opcode::RORRevA => write!(f, "ROR")?,
+            opcode => write!(f, "{:?}", opcode)?,
+        }
+
+        match addressing_mode {
+            AddressingMode::Implied => Ok(()),
+            AddressingMode::Accumulator => write!(f, " A"),
+            AddressingMode::Immediate => write!(f, " #${:02X}", low),
+            AddressingMode::Zeropage | AddressingMode::Relative => write!(f, " ${:02X}", low),
+            AddressingMode::ZeropageXIndexed => write!(f, " ${:02X},X", low),
+            AddressingMode::ZeropageYIndexed => write!(f, " ${:02X},Y", low),
+            AddressingMode::Absolute => write!(f, " ${:04X}", word),
+            AddressingMode::AbsoluteXIndexed => write!(f, " ${:04X},X", word),
+            AddressingMode::AbsoluteYIndexed => write!(f, " ${:04X},Y", word),
+            AddressingMode::Indirect => write!(f, " (${:04X})", word),
+            AddressingMode::IndirectXIndexed => write!(f, " (${:02X},X)", low),
+            AddressingMode::IndirectYIndexed => write!(f, " (${:02X}),Y", low),
+            AddressingMode::ZeropageIndirect => write!(f, " (${:02X})", low),
+            AddressingMode::AbsoluteXIndexedIndirect => write!(f, " (${:04X},X)", word),
+            AddressingMode::ZeropageRelative => {
+                write!(f, " ${:02X},${:02X}", low, self.high_byte.unwrap_or_default())
+            }
+        }
+    }
+}
+
 impl FullOpcode {
     // Returning None means that we tried to parse an illegal instruction
     pub fn try_new(byte: u8) -> Option<FullOpcode> {
-        let low_nibble = byte & 0b0000_1111;
-        let high_nibble = byte >> 4;
-
-        match low_nibble {
-            0x0 => low_nibble_0(high_nibble),
-            0x1 => low_nibble_1(high_nibble),
-            0x2 => low_nibble_2(high_nibble),
-            0x3 => None,
-            0x4 => low_nibble_4(high_nibble),
-            0x5 => low_nibble_5(high_nibble),
-            0x6 => low_nibble_6(high_nibble),
-            0x7 => None,
-            0x8 => low_nibble_8(high_nibble),
-            0x9 => low_nibble_9(high_nibble),
-            0xA => low_nibble_a(high_nibble),
-            0xB => None,
-            0xC => low_nibble_c(high_nibble),
-            0xD => low_nibble_d(high_nibble),
-            0xE => low_nibble_e(high_nibble),
-            0xF => None,
-            _ => unreachable!(),
-        }
+        Self::try_new_for(byte, Variant::default(), Revision::default())
+    }
+
+    /// Decodes an opcode byte as `variant` at `revision` would. Returns None if it is
+    /// illegal.
+    pub fn try_new_for(byte: u8, variant: Variant, revision: Revision) -> Option<FullOpcode> {
+        variant.opcode_table(revision)[byte as usize].map(|info| FullOpcode {
+            opcode: info.opcode,
+            addressing_mode: info.addressing_mode,
+        })
+    }
+}
+
+/// Decodes an opcode byte from scratch, which is only done to build [`OPCODE_TABLE`].
+const fn decode_opcode_byte(byte: u8) -> Option<FullOpcode> {
+    let low_nibble = byte & 0b0000_1111;
+    let high_nibble = byte >> 4;
+
+    match low_nibble {
+        0x0 => low_nibble_0(high_nibble),
+        0x1 => low_nibble_1(high_nibble),
+        0x2 => low_nibble_2(high_nibble),
+        0x3 => None,
+        0x4 => low_nibble_4(high_nibble),
+        0x5 => low_nibble_5(high_nibble),
+        0x6 => low_nibble_6(high_nibble),
+        0x7 => None,
+        0x8 => low_nibble_8(high_nibble),
+        0x9 => low_nibble_9(high_nibble),
+        0xA => low_nibble_a(high_nibble),
+        0xB => None,
+        0xC => low_nibble_c(high_nibble),
+        0xD => low_nibble_d(high_nibble),
+        0xE => low_nibble_e(high_nibble),
+        0xF => None,
+        _ => unreachable!(),
     }
 }
 
-fn low_nibble_0(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_0(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::BRK,
@@ -221,7 +415,7 @@ fn low_nibble_0(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_1(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_1(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ORA,
@@ -291,7 +485,7 @@ fn low_nibble_1(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_2(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_2(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0..=0x9 => return None,
         0xA => FullOpcode {
@@ -303,7 +497,7 @@ fn low_nibble_2(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_4(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_4(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0..=0x1 => return None,
         0x2 => FullOpcode {
@@ -345,7 +539,7 @@ fn low_nibble_4(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_5(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_5(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ORA,
@@ -415,7 +609,7 @@ fn low_nibble_5(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_6(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_6(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ASL,
@@ -485,7 +679,7 @@ fn low_nibble_6(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_8(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_8(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::PHP,
@@ -555,7 +749,7 @@ fn low_nibble_8(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_9(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_9(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ORA,
@@ -622,7 +816,7 @@ fn low_nibble_9(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_a(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_a(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ASL,
@@ -674,7 +868,7 @@ fn low_nibble_a(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_c(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_c(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => return None,
         0x1 => return None,
@@ -720,7 +914,7 @@ fn low_nibble_c(high_nibble: u8) -> Option<FullOpcode> {
     })
 }
 
-fn low_nibble_d(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_d(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ORA,
@@ -789,7 +983,7 @@ fn low_nibble_d(high_nibble: u8) -> Option<FullOpcode> {
         _ => unreachable!(),
     })
 }
-fn low_nibble_e(high_nibble: u8) -> Option<FullOpcode> {
+const fn low_nibble_e(high_nibble: u8) -> Option<FullOpcode> {
     Some(match high_nibble {
         0x0 => FullOpcode {
             opcode: Opcode::ASL,
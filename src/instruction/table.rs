@@ -0,0 +1,154 @@
+use super::wdc65c02::decode_opcode_byte_65c02;
+use super::{decode_opcode_byte, AddressingMode, Opcode};
+
+/// Everything known about an opcode byte before its operands are read.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct OpcodeInfo {
+    pub opcode: Opcode,
+    pub addressing_mode: AddressingMode,
+    /// The cycles taken when no page boundary is crossed and no branch is taken.
+    pub base_cycles: u8,
+}
+
+/// Every opcode byte along with what it decodes to, or None if it is illegal. This is
+/// built at compile time, so decoding is a single lookup.
+pub const OPCODE_TABLE: [Option<OpcodeInfo>; 256] = {
+    let mut table = [None; 256];
+
+    let mut byte = 0;
+    while byte < 256 {
+        if let Some(full_opcode) = decode_opcode_byte(byte as u8) {
+            table[byte] = Some(OpcodeInfo {
+                opcode: full_opcode.opcode,
+                addressing_mode: full_opcode.addressing_mode,
+                base_cycles: base_cycles(full_opcode.opcode, full_opcode.addressing_mode),
+            });
+        }
+        byte += 1;
+    }
+
+    table
+};
+
+/// [`OPCODE_TABLE`] for Rev. A chips, where the ROR opcodes decode to
+/// [`Opcode::RORRevA`].
+pub const REV_A_OPCODE_TABLE: [Option<OpcodeInfo>; 256] = {
+    let mut table = OPCODE_TABLE;
+
+    let mut byte = 0;
+    while byte < 256 {
+        if let Some(info) = &mut table[byte] {
+            if matches!(info.opcode, Opcode::ROR) {
+                info.opcode = Opcode::RORRevA;
+            }
+        }
+        byte += 1;
+    }
+
+    table
+};
+
+/// [`OPCODE_TABLE`] for the WDC 65C02.
+pub const WDC65C02_OPCODE_TABLE: [Option<OpcodeInfo>; 256] = {
+    let mut table = [None; 256];
+
+    let mut byte = 0;
+    while byte < 256 {
+        if let Some(full_opcode) = decode_opcode_byte_65c02(byte as u8) {
+            table[byte] = Some(OpcodeInfo {
+                opcode: full_opcode.opcode,
+                addressing_mode: full_opcode.addressing_mode,
+                base_cycles: base_cycles_65c02(full_opcode.opcode, full_opcode.addressing_mode),
+            });
+        }
+        byte += 1;
+    }
+
+    table
+};
+
+const fn base_cycles(opcode: Opcode, addressing_mode: AddressingMode) -> u8 {
+    // read-modify-write instructions take extra cycles to write the result back
+    let read_modify_write = matches!(
+        opcode,
+        Opcode::ASL
+            | Opcode::LSR
+            | Opcode::ROL
+            | Opcode::ROR
+            | Opcode::RORRevA
+            | Opcode::INC
+            | Opcode::DEC
+    ) || is_bit_instruction(opcode);
+
+    match (opcode, addressing_mode) {
+        (Opcode::BRK, _) => 7,
+        (Opcode::RTI | Opcode::RTS | Opcode::JSR, _) => 6,
+        (Opcode::PHA | Opcode::PHP, _) => 3,
+        (Opcode::PLA | Opcode::PLP, _) => 4,
+        (Opcode::JMP, AddressingMode::Absolute) => 3,
+        (Opcode::STA, AddressingMode::AbsoluteXIndexed | AddressingMode::AbsoluteYIndexed) => 5,
+        (Opcode::STA, AddressingMode::IndirectYIndexed) => 6,
+        (
+            _,
+            AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::Immediate
+            | AddressingMode::Relative,
+        ) => 2,
+        (_, AddressingMode::Zeropage) => 3 + 2 * read_modify_write as u8,
+        (_, AddressingMode::ZeropageXIndexed | AddressingMode::ZeropageYIndexed) => {
+            4 + 2 * read_modify_write as u8
+        }
+        (_, AddressingMode::Absolute) => 4 + 2 * read_modify_write as u8,
+        (_, AddressingMode::AbsoluteXIndexed | AddressingMode::AbsoluteYIndexed) => {
+            4 + 3 * read_modify_write as u8
+        }
+        (_, AddressingMode::Indirect) => 5,
+        (_, AddressingMode::IndirectXIndexed) => 6,
+        (_, AddressingMode::IndirectYIndexed) => 5,
+        (_, AddressingMode::ZeropageIndirect | AddressingMode::ZeropageRelative) => 5,
+        (_, AddressingMode::AbsoluteXIndexedIndirect) => 6,
+    }
+}
+
+/// Returns true for TSB, TRB, RMB and SMB of the 65C02, which all read, modify and write
+/// memory.
+const fn is_bit_instruction(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::TSB
+            | Opcode::TRB
+            | Opcode::RMB0
+            | Opcode::RMB1
+            | Opcode::RMB2
+            | Opcode::RMB3
+            | Opcode::RMB4
+            | Opcode::RMB5
+            | Opcode::RMB6
+            | Opcode::RMB7
+            | Opcode::SMB0
+            | Opcode::SMB1
+            | Opcode::SMB2
+            | Opcode::SMB3
+            | Opcode::SMB4
+            | Opcode::SMB5
+            | Opcode::SMB6
+            | Opcode::SMB7
+    )
+}
+
+const fn base_cycles_65c02(opcode: Opcode, addressing_mode: AddressingMode) -> u8 {
+    match (opcode, addressing_mode) {
+        (Opcode::PHX | Opcode::PHY | Opcode::WAI | Opcode::STP, _) => 3,
+        (Opcode::PLX | Opcode::PLY, _) => 4,
+        // BRA always branches
+        (Opcode::BRA, _) => 3,
+        (Opcode::STZ, AddressingMode::AbsoluteXIndexed) => 5,
+        (Opcode::JMP, AddressingMode::Indirect) => 6,
+        (
+            Opcode::ASL | Opcode::LSR | Opcode::ROL | Opcode::ROR,
+            AddressingMode::AbsoluteXIndexed,
+        ) => 6,
+        _ => base_cycles(opcode, addressing_mode),
+    }
+}
@@ -0,0 +1,100 @@
+//! Decoding of the instructions the WDC 65C02 adds. Its undefined opcodes, which are NOPs
+//! of various lengths on the real chip, are treated as illegal.
+
+use super::{decode_opcode_byte, AddressingMode, FullOpcode, Opcode};
+
+// Indexed by the bit the instruction works on, which is bits 4-6 of the opcode byte
+const RMB: [Opcode; 8] = [
+    Opcode::RMB0,
+    Opcode::RMB1,
+    Opcode::RMB2,
+    Opcode::RMB3,
+    Opcode::RMB4,
+    Opcode::RMB5,
+    Opcode::RMB6,
+    Opcode::RMB7,
+];
+const SMB: [Opcode; 8] = [
+    Opcode::SMB0,
+    Opcode::SMB1,
+    Opcode::SMB2,
+    Opcode::SMB3,
+    Opcode::SMB4,
+    Opcode::SMB5,
+    Opcode::SMB6,
+    Opcode::SMB7,
+];
+const BBR: [Opcode; 8] = [
+    Opcode::BBR0,
+    Opcode::BBR1,
+    Opcode::BBR2,
+    Opcode::BBR3,
+    Opcode::BBR4,
+    Opcode::BBR5,
+    Opcode::BBR6,
+    Opcode::BBR7,
+];
+const BBS: [Opcode; 8] = [
+    Opcode::BBS0,
+    Opcode::BBS1,
+    Opcode::BBS2,
+    Opcode::BBS3,
+    Opcode::BBS4,
+    Opcode::BBS5,
+    Opcode::BBS6,
+    Opcode::BBS7,
+];
+
+const fn full_opcode(opcode: Opcode, addressing_mode: AddressingMode) -> Option<FullOpcode> {
+    Some(FullOpcode {
+        opcode,
+        addressing_mode,
+    })
+}
+
+/// Decodes an opcode byte of the 65C02 from scratch, which is only done to build
+/// [`super::WDC65C02_OPCODE_TABLE`]. Everything but the additions decodes like the 6502.
+pub(super) const fn decode_opcode_byte_65c02(byte: u8) -> Option<FullOpcode> {
+    let bit = ((byte >> 4) & 0b0111) as usize;
+
+    match byte {
+        0x04 => full_opcode(Opcode::TSB, AddressingMode::Zeropage),
+        0x0C => full_opcode(Opcode::TSB, AddressingMode::Absolute),
+        0x14 => full_opcode(Opcode::TRB, AddressingMode::Zeropage),
+        0x1C => full_opcode(Opcode::TRB, AddressingMode::Absolute),
+        0x12 => full_opcode(Opcode::ORA, AddressingMode::ZeropageIndirect),
+        0x32 => full_opcode(Opcode::AND, AddressingMode::ZeropageIndirect),
+        0x52 => full_opcode(Opcode::EOR, AddressingMode::ZeropageIndirect),
+        0x72 => full_opcode(Opcode::ADC, AddressingMode::ZeropageIndirect),
+        0x92 => full_opcode(Opcode::STA, AddressingMode::ZeropageIndirect),
+        0xB2 => full_opcode(Opcode::LDA, AddressingMode::ZeropageIndirect),
+        0xD2 => full_opcode(Opcode::CMP, AddressingMode::ZeropageIndirect),
+        0xF2 => full_opcode(Opcode::SBC, AddressingMode::ZeropageIndirect),
+        0x1A => full_opcode(Opcode::INC, AddressingMode::Accumulator),
+        0x3A => full_opcode(Opcode::DEC, AddressingMode::Accumulator),
+        0x34 => full_opcode(Opcode::BIT, AddressingMode::ZeropageXIndexed),
+        0x3C => full_opcode(Opcode::BIT, AddressingMode::AbsoluteXIndexed),
+        0x89 => full_opcode(Opcode::BIT, AddressingMode::Immediate),
+        0x5A => full_opcode(Opcode::PHY, AddressingMode::Implied),
+        0x7A => full_opcode(Opcode::PLY, AddressingMode::Implied),
+        0xDA => full_opcode(Opcode::PHX, AddressingMode::Implied),
+        0xFA => full_opcode(Opcode::PLX, AddressingMode::Implied),
+        0x64 => full_opcode(Opcode::STZ, AddressingMode::Zeropage),
+        0x74 => full_opcode(Opcode::STZ, AddressingMode::ZeropageXIndexed),
+        0x9C => full_opcode(Opcode::STZ, AddressingMode::Absolute),
+        0x9E => full_opcode(Opcode::STZ, AddressingMode::AbsoluteXIndexed),
+        0x7C => full_opcode(Opcode::JMP, AddressingMode::AbsoluteXIndexedIndirect),
+        0x80 => full_opcode(Opcode::BRA, AddressingMode::Relative),
+        0xCB => full_opcode(Opcode::WAI, AddressingMode::Implied),
+        0xDB => full_opcode(Opcode::STP, AddressingMode::Implied),
+        0x07..=0x77 if byte & 0x0F == 0x07 => full_opcode(RMB[bit], AddressingMode::Zeropage),
+        0x87..=0xF7 if byte & 0x0F == 0x07 => full_opcode(SMB[bit], AddressingMode::Zeropage),
+        0x0F..=0x7F if byte & 0x0F == 0x0F => {
+            full_opcode(BBR[bit], AddressingMode::ZeropageRelative)
+        }
+        0x8F..=0xFF if byte & 0x0F == 0x0F => {
+            full_opcode(BBS[bit], AddressingMode::ZeropageRelative)
+        }
+        _ => decode_opcode_byte(byte),
+    }
+}
@@ -1,6 +1,13 @@
-use instruction::{FullOpcode, Instruction, Opcode};
-use processor_status::ProcessorStatus;
-use sonic_rs::{Deserialize, Serialize};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use call_stack::{CallFrame, CallStack};
+use observer::NoObserver;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 use instruction::execution::system::InterruptState;
 
 pub const STACK_POINTER_STARTING_VALUE: u8 = 0x00;
@@ -8,14 +15,78 @@ pub const NMI_VECTOR_ADDRESS: u16 = 0xFFFA;
 pub const RESET_VECTOR_ADDRESS: u16 = 0xFFFC;
 pub const IRQ_BRK_VECTOR_ADDRESS: u16 = 0xFFFE;
 
+pub mod bus;
+pub mod call_stack;
+pub mod controller;
+#[cfg(feature = "gdbstub")]
+pub mod gdbstub;
 mod instruction;
+pub mod mappers;
+pub mod observer;
 mod processor_status;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod rom;
+#[cfg(feature = "std")]
+mod savestate;
+pub mod state_diff;
+#[cfg(test)]
+pub(crate) mod test_util;
+mod variant;
+
+pub use instruction::{AddressingMode, FullOpcode, Instruction, Opcode, OpcodeInfo, OPCODE_TABLE};
+pub use observer::Observer;
+pub use processor_status::ProcessorStatus;
+#[cfg(feature = "std")]
+pub use savestate::{SavestateError, SAVESTATE_MAGIC, SAVESTATE_VERSION};
+pub use state_diff::{StateDiff, StateMismatch};
+pub use variant::{Revision, Variant};
 
 /// The Cpu Memory Mapper represented as a trait to allow for shared data flexibility when writing a full emulator.
 pub trait Mapper {
     fn read(&self, address: u16) -> u8;
 
+    /// Reads a byte without any of the side effects a normal read may have (such as
+    /// clearing a status register). This is what debuggers use to inspect memory.
+    /// Defaults to [`Self::read`].
+    fn peek(&self, address: u16) -> u8 {
+        self.read(address)
+    }
+
     fn write(&mut self, address: u16, byte: u8);
+
+    /// Writes everything needed to restore the mapper (such as its memory) as part of
+    /// [`Cpu::save_state`]. The default saves nothing, so mappers with memory should
+    /// implement this along with [`Self::load_state`].
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let _ = writer;
+        Ok(())
+    }
+
+    /// Restores what was written by [`Self::save_state`] as part of [`Cpu::load_state`],
+    /// which only changes the cpu if this succeeds. Mappers should likewise read into
+    /// temporaries and keep their state when this fails.
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let _ = reader;
+        Ok(())
+    }
+
+    /// Returns the contiguous buffers backing the memory of the mapper as
+    /// `(start address, bytes)`, which lets [`Cpu::state`] capture memory without reading
+    /// every address. Memory outside of the regions is not part of the state. Defaults to
+    /// `None`, in which case every address is peeked instead.
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        None
+    }
+
+    /// The mutable counterpart of [`Self::snapshot_regions`], used by [`Cpu::from_state`]
+    /// to restore memory without going through [`Self::write`]. Defaults to `None`, in
+    /// which case every byte is written instead.
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        None
+    }
 }
 
 /// The CPU Interrupts represented as a trait to allow for shared data flexibility when writing a full emulator.
@@ -97,8 +168,8 @@ pub trait Interrupts {
 ///
 /// ```
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Copy, Clone, Debug, Default)]
-pub struct Cpu<M: Mapper, I: Interrupts> {
+#[derive(Clone, Debug, Default)]
+pub struct Cpu<M: Mapper, I: Interrupts, O: Observer = NoObserver> {
     pub accumulator: u8,
     pub x: u8,
     pub y: u8,
@@ -109,11 +180,28 @@ pub struct Cpu<M: Mapper, I: Interrupts> {
     pub memory_mapper: M,
     pub interrupts: I,
     pub initialized: bool,
+    /// The shadow call stack, which is only tracked when enabled with
+    /// [`Self::enable_call_stack`].
+    pub call_stack: Option<CallStack>,
+    /// The total amount of cpu cycles ran.
+    pub cycles: u64,
+    /// Gets notified of everything the cpu does. See [`Observer`].
+    pub observer: O,
+    /// The chip being emulated, which defaults to the 2A03 of the NES.
+    pub variant: Variant,
+    /// The revision of the chip, which defaults to the standard one. It is ignored by the
+    /// 65C02.
+    pub revision: Revision,
+    /// Set by the WAI instruction of the 65C02 until an interrupt is requested.
+    pub waiting: bool,
+    /// Set by the STP instruction of the 65C02 until the cpu is reset.
+    pub stopped: bool,
 }
 
 /// The state of the CPU. The `ram` field is the non-zero memory
-/// locations in [address, value].
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, Ord, PartialOrd, Default)]
+/// locations as (address, value), which serializes as [address, value].
+#[derive(Debug, Clone, Eq, Ord, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CpuState {
     pub pc: u16,
     pub s: u8,
@@ -121,10 +209,44 @@ pub struct CpuState {
     pub x: u8,
     pub y: u8,
     pub p: u8,
-    // The non-zero memory
-    /// locations in [address, value] form. The value will be
-    /// within a u16
-    pub ram: Vec<Vec<u16>>,
+    /// The non-zero memory locations in (address, value) form. [`Cpu::state`] returns
+    /// them sorted by address.
+    pub ram: Vec<(u16, u8)>,
+}
+
+impl CpuState {
+    /// Returns the non-zero memory sorted by address, only cloning it if it is not
+    /// already in that form.
+    fn normalized_ram(&self) -> Cow<'_, [(u16, u8)]> {
+        let normalized = self.ram.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && self.ram.iter().all(|&(_, value)| value != 0);
+
+        match normalized {
+            true => Cow::Borrowed(&self.ram),
+            false => {
+                let mut cloned = self.ram.clone();
+                cloned.retain(|&(_, value)| value != 0);
+                cloned.sort_by_key(|&(address, _)| address);
+                Cow::Owned(cloned)
+            }
+        }
+    }
+
+    /// Returns a 64-bit FNV-1a hash of the registers and the non-zero memory, which is the
+    /// same for states that compare equal and stable across platforms and builds.
+    pub fn checksum(&self) -> u64 {
+        let registers = [self.s, self.a, self.x, self.y, self.p];
+        let ram = self.normalized_ram();
+
+        let bytes = self.pc.to_le_bytes().into_iter().chain(registers).chain(
+            ram.iter()
+                .flat_map(|&(address, value)| [address as u8, (address >> 8) as u8, value]),
+        );
+
+        bytes.fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+    }
 }
 
 impl PartialEq for CpuState {
@@ -136,26 +258,7 @@ impl PartialEq for CpuState {
             && (self.y == other.y)
             && (self.p == other.p);
 
-        // Sort the ram by index. This is its normalized state
-        let self_normalized_ram = {
-            let mut cloned = self.ram.clone();
-            cloned.sort_by(|a, b| a[0].cmp(&b[0]));
-            cloned
-                .into_iter()
-                .filter(|x| x[1] != 0)
-                .collect::<Vec<Vec<u16>>>()
-        };
-
-        let other_normalized_ram = {
-            let mut cloned = other.ram.clone();
-            cloned.sort_by(|a, b| a[0].cmp(&b[0]));
-            cloned
-                .into_iter()
-                .filter(|x| x[1] != 0)
-                .collect::<Vec<Vec<u16>>>()
-        };
-
-        other_fields_match && (self_normalized_ram == other_normalized_ram)
+        other_fields_match && (self.normalized_ram() == other.normalized_ram())
     }
 }
 
@@ -175,15 +278,46 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
             memory_mapper,
             interrupts,
             initialized: false,
+            call_stack: None,
+            cycles: 0,
+            observer: NoObserver,
+            variant: Variant::default(),
+            revision: Revision::default(),
+            waiting: false,
+            stopped: false,
         }
     }
 
-    pub fn from_state(cpu_state: CpuState, mut memory_mapper: M, interrupts: I) -> Self {
-        for chunk in &cpu_state.ram {
-            let address = chunk[0];
-            let value = chunk[1];
+    /// Creates an initialized Cpu from a state, writing its memory into the mapper. Returns
+    /// an error listing the differences if the state read back from the cpu does not match.
+    pub fn from_state(
+        cpu_state: CpuState,
+        mut memory_mapper: M,
+        interrupts: I,
+    ) -> Result<Self, StateMismatch> {
+        // memory outside of the regions is still written normally
+        let unmapped = match memory_mapper.restore_regions() {
+            Some(mut regions) => {
+                let mut unmapped = Vec::new();
+
+                for &(address, value) in &cpu_state.ram {
+                    let region = regions.iter_mut().find(|(start, bytes)| {
+                        address >= *start && ((address - start) as usize) < bytes.len()
+                    });
+
+                    match region {
+                        Some((start, bytes)) => bytes[(address - *start) as usize] = value,
+                        None => unmapped.push((address, value)),
+                    }
+                }
 
-            memory_mapper.write(address, value as u8);
+                unmapped
+            }
+            None => cpu_state.ram.clone(),
+        };
+
+        for (address, value) in unmapped {
+            memory_mapper.write(address, value);
         }
 
         let cpu = Self {
@@ -197,26 +331,80 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
             memory_mapper,
             interrupts,
             initialized: true,
+            call_stack: None,
+            cycles: 0,
+            observer: NoObserver,
+            variant: Variant::default(),
+            revision: Revision::default(),
+            waiting: false,
+            stopped: false,
         };
 
         // sanity check
-        assert_eq!(cpu.state(), cpu_state);
+        let diff = cpu.state().diff(&cpu_state);
+        match diff.is_empty() {
+            true => Ok(cpu),
+            false => Err(StateMismatch { diff }),
+        }
+    }
+
+}
+
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
+    /// Replaces the observer of the cpu, keeping everything else.
+    pub fn with_observer<P: Observer>(self, observer: P) -> Cpu<M, I, P> {
+        Cpu {
+            accumulator: self.accumulator,
+            x: self.x,
+            y: self.y,
+            stack_pointer: self.stack_pointer,
+            program_counter: self.program_counter,
+            registers: self.registers,
+            processor_status: self.processor_status,
+            memory_mapper: self.memory_mapper,
+            interrupts: self.interrupts,
+            initialized: self.initialized,
+            call_stack: self.call_stack,
+            cycles: self.cycles,
+            observer,
+            variant: self.variant,
+            revision: self.revision,
+            waiting: self.waiting,
+            stopped: self.stopped,
+        }
+    }
 
-        cpu
+    /// Sets the chip being emulated. See [`Variant`].
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
     }
 
-    pub fn state(&self) -> CpuState {
-        let ram = {
-            let mut ram = Vec::new();
+    /// Sets the revision of the chip being emulated. See [`Revision`].
+    pub fn with_revision(mut self, revision: Revision) -> Self {
+        self.revision = revision;
+        self
+    }
 
-            for i in 0..=65535 {
-                let value = self.read(i);
-                if value != 0 {
-                    ram.push(vec![i, value as u16])
+    pub fn state(&self) -> CpuState {
+        let ram = match self.memory_mapper.snapshot_regions() {
+            Some(regions) => {
+                let mut ram = Vec::new();
+
+                for (start, bytes) in regions {
+                    let non_zero = bytes.iter().enumerate().filter(|(_, &value)| value != 0);
+                    for (offset, &value) in non_zero {
+                        ram.push((start.wrapping_add(offset as u16), value));
+                    }
                 }
-            }
 
-            ram
+                ram.sort_unstable_by_key(|&(address, _)| address);
+                ram
+            }
+            None => (0..=65535)
+                .map(|address| (address, self.peek(address)))
+                .filter(|&(_, value)| value != 0)
+                .collect(),
         };
 
         CpuState {
@@ -238,8 +426,10 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         self.processor_status.clear_overflow_flag();
         self.processor_status.clear_negative_flag();
         self.processor_status.clear_break_flag();
+        self.waiting = false;
+        self.stopped = false;
 
-        self.instruction_brk(InterruptState::Reset);
+        self.cycles += self.instruction_brk(InterruptState::Reset) as u64;
     }
 
     /// Initializes the CPU to a state ready to run instructions. The memory mapper initialization must be
@@ -253,12 +443,44 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         self.initialized
     }
 
+    /// Starts tracking calls and interrupts in a shadow call stack, which can be
+    /// inspected with [`Self::backtrace`]. Frames entered before this is called are not known.
+    pub fn enable_call_stack(&mut self) {
+        self.call_stack = Some(CallStack::new());
+    }
+
+    pub fn disable_call_stack(&mut self) {
+        self.call_stack = None;
+    }
+
+    /// Returns the frames of the shadow call stack, outermost first. This is empty if
+    /// the call stack is not enabled.
+    pub fn backtrace(&self) -> &[CallFrame] {
+        match &self.call_stack {
+            Some(call_stack) => call_stack.frames(),
+            None => &[],
+        }
+    }
+
     /// Runs a full instruction cycle. Returns the amount of
     /// cpu cycles taken.
     pub fn cycle(&mut self) -> u8 {
+        let cycles = self.run_instruction_cycle();
+        self.cycles += cycles as u64;
+
+        cycles
+    }
+
+    fn run_instruction_cycle(&mut self) -> u8 {
+        // STP stops the clock until a reset
+        if self.stopped {
+            return 1;
+        }
+
         // check for non-maskable interrupts
         if self.interrupts.non_maskable_interrupt_state() {
             self.interrupts.set_non_maskable_interrupt_state(false);
+            self.waiting = false;
             return self.instruction_brk(InterruptState::NonMaskableInterrupt)
         } 
 
@@ -266,225 +488,75 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         let interrupts_disabled = (self.processor_status.0 & 0b0000_0100) != 0;
         if self.interrupts.interrupt_state() && !interrupts_disabled {
             self.interrupts.set_interrupt_state(false);
+            self.waiting = false;
             return self.instruction_brk(InterruptState::MaskableInterrupt)
         }
 
+        // WAI also ends on an interrupt that is disabled, which is then not serviced
+        if self.waiting {
+            match self.interrupts.interrupt_state() {
+                true => self.waiting = false,
+                false => return 1,
+            }
+        }
+
         // normal fetch
-        let instruction = self.fetch().unwrap();
+        let (byte, instruction) = self.fetch().unwrap();
 
         // execute
-        self.execute(instruction)
+        self.execute(byte, instruction)
     }
     
     // returns true on the second return value if instruction was executed successfully
     pub fn cycle_debug(&mut self) -> (u8, bool, Option<Instruction>) {
-        let instruction = match self.fetch() {
+        let (byte, instruction) = match self.fetch() {
             Some(x) => x,
             None => return (0, false, None),
         };
         //self.pretty_print_cpu_state(instruction);
 
         // execute
-        (self.execute(instruction), true, Some(instruction))
-    }
-
-    /// Fetches the next instruction and updates the program counter.
-    fn fetch(&mut self) -> Option<Instruction> {
-        let full_opcode = match FullOpcode::try_new(self.memory_mapper.read(self.program_counter)) {
-            Some(x) => x,
-            None => return None,
-        };
+        let cycles = self.execute(byte, instruction);
+        self.cycles += cycles as u64;
 
-        let mut bytes_required = full_opcode.addressing_mode.bytes_required();
+        (cycles, true, Some(instruction))
+    }
 
-        // BRK has 1 byte of debugging information right after it, giving
-        // it a size of 2.
-        if full_opcode.opcode == Opcode::BRK {
-            bytes_required += 1;
-        }
+    /// Fetches the next instruction and updates the program counter. Returns the opcode
+    /// byte along with the instruction.
+    fn fetch(&mut self) -> Option<(u8, Instruction)> {
+        let address = self.program_counter;
+        let byte = self.observed_read(address);
+        let info = self.variant.opcode_table(self.revision)[byte as usize]?;
+        let instruction =
+            Instruction::with_operands(address, info, |address| self.observed_read(address));
 
-        // Low byte comes first as words are in little-endian
-        let (low_byte, high_byte) = match bytes_required {
-            1 => (None, None),
-            2 => (
-                Some(
-                    self.memory_mapper
-                        .read(self.program_counter.wrapping_add(1)),
-                ),
-                None,
-            ),
-            3 => (
-                Some(
-                    self.memory_mapper
-                        .read(self.program_counter.wrapping_add(1)),
-                ),
-                Some(
-                    self.memory_mapper
-                        .read(self.program_counter.wrapping_add(2)),
-                ),
-            ),
-            _ => unreachable!(),
-        };
+        self.observer.on_instruction(address, &instruction);
 
         // Decide how much we need to increment the PC
-        self.program_counter = self.program_counter.wrapping_add(bytes_required);
+        self.program_counter = self.program_counter.wrapping_add(instruction.size());
 
-        Some(Instruction {
-            opcode: full_opcode.opcode,
-            addressing_mode: full_opcode.addressing_mode,
-            low_byte,
-            high_byte,
+        Some((byte, instruction))
+    }
+
+    /// Decodes the instruction at the given address without executing it. Memory is
+    /// read with [`Mapper::peek`]. Returns None if the opcode is illegal.
+    pub fn disassemble(&self, address: u16) -> Option<Instruction> {
+        Instruction::decode_for(address, self.variant, self.revision, |address| {
+            self.peek(address)
         })
     }
 
-    /// Executes the instruction and returns the amount of machine cycles that it took.
-    fn execute(&mut self, instruction: Instruction) -> u8 {
-        match instruction.opcode {
-            Opcode::ADC => self.instruction_adc(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::AND => self.instruction_and(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::ASL => self.instruction_asl(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::BCC => self.instruction_bcc(instruction.low_byte),
-            Opcode::BCS => self.instruction_bcs(instruction.low_byte),
-            Opcode::BEQ => self.instruction_beq(instruction.low_byte),
-            Opcode::BIT => self.instruction_bit(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::BMI => self.instruction_bmi(instruction.low_byte),
-            Opcode::BNE => self.instruction_bne(instruction.low_byte),
-            Opcode::BPL => self.instruction_bpl(instruction.low_byte),
-            Opcode::BRK => self.instruction_brk(InterruptState::Inactive),
-            Opcode::BVC => self.instruction_bvc(instruction.low_byte),
-            Opcode::BVS => self.instruction_bvs(instruction.low_byte),
-            Opcode::CLC => self.instruction_clc(),
-            Opcode::CLD => self.instruction_cld(),
-            Opcode::CLI => self.instruction_cli(),
-            Opcode::CLV => self.instruction_clv(),
-            Opcode::CMP => self.instruction_cmp(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::CPX => self.instruction_cpx(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::CPY => self.instruction_cpy(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::DEC => self.instruction_dec(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::DEX => self.instruction_dex(),
-            Opcode::DEY => self.instruction_dey(),
-            Opcode::EOR => self.instruction_eor(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::INC => self.instruction_inc(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::INX => self.instruction_inx(),
-            Opcode::INY => self.instruction_iny(),
-            Opcode::JMP => self.instruction_jmp(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::JSR => self.instruction_jsr(instruction.low_byte, instruction.high_byte),
-            Opcode::LDA => self.instruction_lda(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::LDX => self.instruction_ldx(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::LDY => self.instruction_ldy(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::LSR => self.instruction_lsr(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::NOP => self.instruction_nop(),
-            Opcode::ORA => self.instruction_ora(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::PHA => self.instruction_pha(),
-            Opcode::PHP => self.instruction_php(),
-            Opcode::PLA => self.instruction_pla(),
-            Opcode::PLP => self.instruction_plp(),
-            Opcode::ROL => self.instruction_rol(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::ROR => self.instruction_ror(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::RTI => self.instruction_rti(),
-            Opcode::RTS => self.instruction_rts(),
-            Opcode::SBC => self.instruction_sbc(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::SEC => self.instruction_sec(),
-            Opcode::SED => self.instruction_sed(),
-            Opcode::SEI => self.instruction_sei(),
-            Opcode::STA => self.instruction_sta(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::STX => self.instruction_stx(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::STY => self.instruction_sty(
-                instruction.addressing_mode,
-                instruction.low_byte,
-                instruction.high_byte,
-            ),
-            Opcode::TAX => self.instruction_tax(),
-            Opcode::TAY => self.instruction_tay(),
-            Opcode::TSX => self.instruction_tsx(),
-            Opcode::TXA => self.instruction_txa(),
-            Opcode::TXS => self.instruction_txs(),
-            Opcode::TYA => self.instruction_tya(),
-        }
+    /// Executes the instruction of an opcode byte and returns the amount of machine cycles
+    /// that it took.
+    fn execute(&mut self, byte: u8, instruction: Instruction) -> u8 {
+        let handlers = match (self.variant, self.revision) {
+            (Variant::Ricoh2A03 | Variant::Nmos6502, Revision::Standard) => &Self::HANDLERS,
+            (Variant::Ricoh2A03 | Variant::Nmos6502, Revision::RevA) => &Self::REV_A_HANDLERS,
+            (Variant::Wdc65C02, _) => &Self::WDC65C02_HANDLERS,
+        };
+
+        (handlers[byte as usize])(self, instruction)
     }
 
     // Shortcuts to read a byte from the memory mapper because
@@ -493,13 +565,27 @@ impl<M: Mapper, I: Interrupts> Cpu<M, I> {
         self.memory_mapper.read(address)
     }
 
+    /// Reads a byte like [`Self::read`] and reports it to the observer. Everything the cpu
+    /// reads while executing goes through this.
+    pub(crate) fn observed_read(&mut self, address: u16) -> u8 {
+        let value = self.memory_mapper.read(address);
+        self.observer.on_read(address, value);
+        value
+    }
+
+    /// Reads a byte through [`Mapper::peek`], without any read side effects.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.memory_mapper.peek(address)
+    }
+
     // Shortcuts to read a byte from the memory mapper because
     // we use this a lot.
     pub fn write(&mut self, address: u16, value: u8) {
         self.memory_mapper.write(address, value);
+        self.observer.on_write(address, value);
     }
 
-    #[allow(dead_code)]
+    #[cfg(feature = "std")]
     /// Pretty prints the full state of the Cpu. Meant to be used after fetch but
     /// before execution to work correctly.
     pub fn pretty_print_cpu_state(&self, instruction: Instruction) {
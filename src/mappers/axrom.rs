@@ -0,0 +1,132 @@
+use super::{bank_offset, check_rom, PrgRam, BUS_CONFLICTS_SUBMAPPER};
+use crate::bus::Cartridge;
+use crate::rom::{Mirroring, Rom, RomError};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+
+/// AxROM (mapper 7), which switches all 32KB of PRG-ROM at once and selects one of the two
+/// nametables for single screen mirroring.
+///
+/// | Address       | Contents                                |
+/// |---------------|-----------------------------------------|
+/// | $6000 - $7FFF | PRG-RAM, if the header has any          |
+/// | $8000 - $FFFF | a switchable 32KB PRG-ROM bank          |
+///
+/// Writing to $8000-$FFFF selects the bank with bits 0-2 and the nametable with bit 4.
+/// CHR is 8KB and not banked.
+#[derive(Clone, Debug)]
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    bus_conflicts: bool,
+    register: u8,
+}
+
+impl Axrom {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        // a 16KB ROM is mirrored to fill the bank
+        check_rom(rom, 7, 0x4000)?;
+
+        let mut prg_rom = rom.prg_rom.clone();
+        if prg_rom.len() == 0x4000 {
+            prg_rom.extend_from_within(..);
+        }
+
+        if !prg_rom.len().is_multiple_of(PRG_BANK_SIZE) {
+            return Err(RomError::InvalidPrgRomSize(rom.prg_rom.len()));
+        }
+
+        Ok(Self {
+            prg_rom,
+            prg_ram: PrgRam::new(rom),
+            bus_conflicts: rom.submapper == BUS_CONFLICTS_SUBMAPPER,
+            register: 0,
+        })
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        match self.register & 0x10 != 0 {
+            true => Mirroring::SingleScreenUpper,
+            false => Mirroring::SingleScreenLower,
+        }
+    }
+
+    /// Returns the offset into CHR memory of a PPU address in $0000-$1FFF.
+    pub fn chr_offset(&self, address: u16) -> usize {
+        address as usize & 0x1FFF
+    }
+
+    fn prg_offset(&self, address: u16) -> usize {
+        let bank = (self.register & 0x07) as usize;
+        bank_offset(bank, PRG_BANK_SIZE, self.prg_rom.len()) + (address as usize & 0x7FFF)
+    }
+}
+
+impl Cartridge for Axrom {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.read(address),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.write(address, byte),
+            0x8000..=0xFFFF => {
+                self.register = match self.bus_conflicts {
+                    true => byte & self.prg_rom[self.prg_offset(address)],
+                    false => byte,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.prg_ram.save_state(writer)?;
+        writer.write_all(&[self.register])
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut register = [0];
+        let prg_ram = self.prg_ram.read_state(reader)?;
+        reader.read_exact(&mut register)?;
+        self.prg_ram.bytes = prg_ram;
+        self.register = register[0];
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mappers::test::rom;
+
+    #[test]
+    fn test_axrom() {
+        let mut axrom = Axrom::new(&rom(7, 0x40000, 0)).unwrap();
+        assert_eq!(axrom.read(0x8000), Some(0));
+        assert_eq!(axrom.read(0xFFFF), Some(3));
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.write(0x8000, 0x17);
+        assert_eq!(axrom.read(0x8000), Some(28));
+        assert_eq!(axrom.read(0xFFFF), Some(31));
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+    }
+}
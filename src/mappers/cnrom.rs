@@ -0,0 +1,131 @@
+use super::{check_rom, chr_size, PrgRam, BUS_CONFLICTS_SUBMAPPER};
+use crate::bus::Cartridge;
+use crate::rom::{Mirroring, Rom, RomError};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// CNROM (mapper 3), which has NROM's 16KB or 32KB of PRG-ROM and switches the whole 8KB
+/// of CHR.
+///
+/// | Address       | Contents                                |
+/// |---------------|-----------------------------------------|
+/// | $6000 - $7FFF | PRG-RAM, if the header has any          |
+/// | $8000 - $FFFF | 16KB or 32KB of PRG-ROM, mirrored       |
+///
+/// Writing to $8000-$FFFF selects the CHR bank.
+#[derive(Clone, Debug)]
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr_size: usize,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        check_rom(rom, 3, 0x4000)?;
+
+        if rom.prg_rom.len() > 0x8000 {
+            return Err(RomError::InvalidPrgRomSize(rom.prg_rom.len()));
+        }
+
+        Ok(Self {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram: PrgRam::new(rom),
+            chr_size: chr_size(rom),
+            mirroring: rom.mirroring,
+            bus_conflicts: rom.submapper == BUS_CONFLICTS_SUBMAPPER,
+            chr_bank: 0,
+        })
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    /// Returns the offset into CHR memory of a PPU address in $0000-$1FFF.
+    pub fn chr_offset(&self, address: u16) -> usize {
+        (self.chr_bank as usize * CHR_BANK_SIZE + (address as usize & 0x1FFF)) % self.chr_size
+    }
+
+    fn prg_offset(&self, address: u16) -> usize {
+        (address as usize - 0x8000) % self.prg_rom.len()
+    }
+}
+
+impl Cartridge for Cnrom {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.read(address),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.write(address, byte),
+            0x8000..=0xFFFF => {
+                self.chr_bank = match self.bus_conflicts {
+                    true => byte & self.prg_rom[self.prg_offset(address)],
+                    false => byte,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.prg_ram.save_state(writer)?;
+        writer.write_all(&[self.chr_bank])
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut chr_bank = [0];
+        let prg_ram = self.prg_ram.read_state(reader)?;
+        reader.read_exact(&mut chr_bank)?;
+        self.prg_ram.bytes = prg_ram;
+        self.chr_bank = chr_bank[0];
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mappers::test::rom;
+
+    #[test]
+    fn test_cnrom() {
+        let mut cnrom = Cnrom::new(&rom(3, 0x4000, 0x8000)).unwrap();
+        assert_eq!(cnrom.read(0xC001), Some(0));
+        assert_eq!(cnrom.chr_offset(0x1234), 0x1234);
+
+        cnrom.write(0x8000, 2);
+        assert_eq!(cnrom.chr_offset(0x1234), 0x5234);
+
+        // there are only 4 banks
+        cnrom.write(0x8000, 5);
+        assert_eq!(cnrom.chr_offset(0x0000), 0x2000);
+
+        assert_eq!(
+            Cnrom::new(&rom(3, 0x10000, 0x8000)).err(),
+            Some(RomError::InvalidPrgRomSize(0x10000))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use super::{check_rom, chr_size, PrgRam};
+use crate::bus::Cartridge;
+use crate::rom::{Mirroring, Rom, RomError};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+/// MMC1 (mapper 1), whose registers are written one bit at a time through a shift register.
+///
+/// | Address       | Contents                                              |
+/// |---------------|-------------------------------------------------------|
+/// | $6000 - $7FFF | PRG-RAM, unless disabled by bit 4 of the PRG bank     |
+/// | $8000 - $BFFF | a 16KB PRG-ROM bank, or the first half of a 32KB bank |
+/// | $C000 - $FFFF | a 16KB PRG-ROM bank, or the last half of a 32KB bank  |
+///
+/// Five writes to $8000-$FFFF with bit 0 set to the next bit (low bit first) load the
+/// register selected by the address of the last write: the control at $8000, the CHR
+/// banks at $A000 and $C000 and the PRG bank at $E000. A write with bit 7 set resets the
+/// shift register. Boards with 512KB of PRG-ROM (SUROM) select the 256KB half with bit 4
+/// of the first CHR bank.
+///
+/// MMC1 ignores a write on the cycle right after another one, which is how it sees only the
+/// first of the two writes of a read-modify-write instruction. This is not modeled, as the
+/// cpu only makes the final write of such instructions, so a game that resets the mapper
+/// with an `INC` of a ROM byte of $FF will shift in the incremented value instead.
+#[derive(Clone, Debug)]
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr_size: usize,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        check_rom(rom, 1, PRG_BANK_SIZE)?;
+
+        Ok(Self {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram: PrgRam::new(rom),
+            chr_size: chr_size(rom),
+            shift_register: 0,
+            shift_count: 0,
+            // the last bank is fixed at $C000 on power up
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+        })
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    /// Returns the offset into CHR memory of a PPU address in $0000-$1FFF.
+    pub fn chr_offset(&self, address: u16) -> usize {
+        let high = address >= 0x1000;
+        let bank = match (self.control & 0x10 != 0, high) {
+            // 8KB mode, ignoring the low bit of the bank
+            (false, _) => (self.chr_bank_0 & 0x1E) as usize + high as usize,
+            (true, false) => self.chr_bank_0 as usize,
+            (true, true) => self.chr_bank_1 as usize,
+        };
+
+        (bank * CHR_BANK_SIZE + (address as usize & 0xFFF)) % self.chr_size
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    /// Maps an address in $8000-$FFFF to an offset into the PRG-ROM.
+    fn prg_offset(&self, address: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let prg_bank = (self.prg_bank & 0x0F) as usize;
+        let last_bank = banks.min(16) - 1;
+
+        let bank = match ((self.control >> 2) & 0b11, address >= 0xC000) {
+            // 32KB mode, ignoring the low bit of the bank
+            (0 | 1, high) => (prg_bank & 0x0E) + high as usize,
+            (2, false) => 0,
+            (2, true) => prg_bank,
+            (_, false) => prg_bank,
+            (_, true) => last_bank,
+        };
+
+        // SUROM's 256KB halves
+        let outer_bank = match banks > 16 {
+            true => (self.chr_bank_0 & 0x10) as usize,
+            false => 0,
+        };
+
+        ((outer_bank + bank) % banks) * PRG_BANK_SIZE + (address as usize & 0x3FFF)
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8) {
+        if byte & 0x80 != 0 {
+            self.shift_register = 0;
+            self.shift_count = 0;
+            self.control |= 0x0C;
+            return;
+        }
+
+        self.shift_register |= (byte & 1) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count < 5 {
+            return;
+        }
+
+        let value = self.shift_register;
+        self.shift_register = 0;
+        self.shift_count = 0;
+
+        match address {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank_0 = value,
+            0xC000..=0xDFFF => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+}
+
+impl Cartridge for Mmc1 {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(address),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.write(address, byte),
+            0x8000..=0xFFFF => self.write_register(address, byte),
+            _ => {}
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.prg_ram.save_state(writer)?;
+        writer.write_all(&[
+            self.shift_register,
+            self.shift_count,
+            self.control,
+            self.chr_bank_0,
+            self.chr_bank_1,
+            self.prg_bank,
+        ])
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut registers = [0; 6];
+        let prg_ram = self.prg_ram.read_state(reader)?;
+        reader.read_exact(&mut registers)?;
+
+        // a full shift register is written to its register right away
+        if registers[1] > 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid MMC1 shift count",
+            ));
+        }
+
+        self.prg_ram.bytes = prg_ram;
+        [
+            self.shift_register,
+            self.shift_count,
+            self.control,
+            self.chr_bank_0,
+            self.chr_bank_1,
+            self.prg_bank,
+        ] = registers;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mappers::test::rom;
+
+    fn write_serial(mmc1: &mut Mmc1, address: u16, value: u8) {
+        for bit in 0..5 {
+            mmc1.write(address, (value >> bit) & 1);
+        }
+    }
+
+    #[test]
+    fn test_mmc1_banking() {
+        let mut mmc1 = Mmc1::new(&rom(1, 0x20000, 0x8000)).unwrap();
+        // power up fixes the last bank at $C000
+        assert_eq!(mmc1.read(0xC000), Some(14));
+
+        write_serial(&mut mmc1, 0xE000, 3);
+        assert_eq!(mmc1.read(0x8001), Some(6));
+
+        // switch to 32KB PRG mode, 4KB CHR mode and vertical mirroring
+        write_serial(&mut mmc1, 0x8000, 0b1_0010);
+        assert_eq!(mmc1.read(0x8000), Some(4));
+        assert_eq!(mmc1.read(0xC000), Some(6));
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+
+        write_serial(&mut mmc1, 0xA000, 3);
+        write_serial(&mut mmc1, 0xC000, 5);
+        assert_eq!(mmc1.chr_offset(0x0010), 0x3010);
+        assert_eq!(mmc1.chr_offset(0x1010), 0x5010);
+
+        // a reset in the middle of a write starts over
+        mmc1.write(0xE000, 1);
+        mmc1.write(0xE000, 0x80);
+        write_serial(&mut mmc1, 0xE000, 2);
+        assert_eq!(mmc1.read(0x8000), Some(4));
+        assert_eq!(mmc1.read(0xC000), Some(14));
+    }
+
+    #[test]
+    fn test_mmc1_prg_ram() {
+        let mut mmc1 = Mmc1::new(&rom(1, 0x80000, 0)).unwrap();
+        mmc1.write(0x6000, 0x42);
+        assert_eq!(mmc1.read(0x6000), Some(0x42));
+        assert_eq!(mmc1.battery_ram().unwrap()[0], 0x42);
+
+        write_serial(&mut mmc1, 0xE000, 0x10);
+        assert_eq!(mmc1.read(0x6000), None);
+
+        // the second 256KB of a SUROM board
+        write_serial(&mut mmc1, 0xA000, 0x10);
+        assert_eq!(mmc1.read(0x8000), Some(32));
+        assert_eq!(mmc1.read(0xC000), Some(62));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_mmc1_state() {
+        let mut mmc1 = Mmc1::new(&rom(1, 0x40000, 0)).unwrap();
+        mmc1.write(0x6000, 0x42);
+        let mut state = Vec::new();
+        mmc1.save_state(&mut state).unwrap();
+
+        // a truncated state keeps the PRG-RAM
+        mmc1.write(0x6000, 0x24);
+        assert!(mmc1.load_state(&mut &state[..state.len() - 1]).is_err());
+        assert_eq!(mmc1.read(0x6000), Some(0x24));
+
+        // the shift count has to be below 5
+        let shift_count = state.len() - 5;
+        state[shift_count] = 8;
+        assert!(mmc1.load_state(&mut state.as_slice()).is_err());
+        assert_eq!(mmc1.read(0x6000), Some(0x24));
+
+        state[shift_count] = 0;
+        mmc1.load_state(&mut state.as_slice()).unwrap();
+        assert_eq!(mmc1.read(0x6000), Some(0x42));
+    }
+}
@@ -0,0 +1,417 @@
+use super::{check_rom, chr_size, PrgRam};
+use crate::bus::Cartridge;
+use crate::rom::{Mirroring, Rom, RomError};
+use crate::Interrupts;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x400;
+
+/// How long A12 has to stay low for its next rise to clock the IRQ counter, which is about
+/// 3 cpu cycles. This filters out the toggling during sprite pattern fetches.
+const A12_LOW_PPU_CYCLES: u64 = 9;
+
+/// MMC3 (mapper 4), with four 8KB PRG-ROM banks, eight CHR banks and a scanline counter
+/// that raises IRQs.
+///
+/// | Address       | Contents                                               |
+/// |---------------|--------------------------------------------------------|
+/// | $6000 - $7FFF | PRG-RAM, unless disabled by $A001                      |
+/// | $8000 - $9FFF | R6, or the second to last bank when the PRG mode is 1  |
+/// | $A000 - $BFFF | R7                                                     |
+/// | $C000 - $DFFF | the second to last bank, or R6 when the PRG mode is 1  |
+/// | $E000 - $FFFF | the last bank                                          |
+///
+/// The registers are at the even and odd addresses of each 8KB: bank select and bank data
+/// at $8000, mirroring and PRG-RAM protect at $A000, IRQ latch and reload at $C000, and
+/// IRQ disable and enable at $E000.
+///
+/// The PPU drives the IRQ counter with [`Mmc3::set_a12`], which asserts the IRQ line when
+/// the counter reaches 0. The line stays asserted until the IRQ is acknowledged by disabling
+/// IRQs through $E000, which only the bus can see, so
+/// [`NesBus::update_irq`](crate::bus::NesBus::update_irq) has to be called after every
+/// instruction to release it.
+#[derive(Clone, Debug)]
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr_size: usize,
+    four_screen: bool,
+    bank_select: u8,
+    /// R0-R7.
+    banks: [u8; 8],
+    mirroring: u8,
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12_high: bool,
+    /// The PPU cycle A12 last went low on.
+    a12_fell_at: u64,
+}
+
+impl Mmc3 {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        check_rom(rom, 4, 0x4000)?;
+
+        Ok(Self {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram: PrgRam::new(rom),
+            chr_size: chr_size(rom),
+            four_screen: rom.mirroring == Mirroring::FourScreen,
+            bank_select: 0,
+            banks: [0; 8],
+            mirroring: 0,
+            // the PRG-RAM starts enabled, as games without a battery never enable it
+            prg_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_high: false,
+            a12_fell_at: 0,
+        })
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        match (self.four_screen, self.mirroring & 1) {
+            (true, _) => Mirroring::FourScreen,
+            (false, 0) => Mirroring::Vertical,
+            (false, _) => Mirroring::Horizontal,
+        }
+    }
+
+    /// Returns the offset into CHR memory of a PPU address in $0000-$1FFF.
+    pub fn chr_offset(&self, address: u16) -> usize {
+        // the inversion swaps the 2KB banks at $0000 with the 1KB banks at $1000
+        let address = match self.bank_select & 0x80 != 0 {
+            true => address as usize ^ 0x1000,
+            false => address as usize,
+        };
+
+        let bank = match (address >> 10) & 0b111 {
+            0 => self.banks[0] & 0xFE,
+            1 => self.banks[0] | 1,
+            2 => self.banks[1] & 0xFE,
+            3 => self.banks[1] | 1,
+            bank => self.banks[bank - 2],
+        };
+
+        (bank as usize * CHR_BANK_SIZE + (address & 0x3FF)) % self.chr_size
+    }
+
+    /// Whether an IRQ has been raised and not yet acknowledged by disabling IRQs.
+    pub fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    /// Updates the level of PPU address line A12, where `ppu_cycle` is a running count of
+    /// PPU cycles. The IRQ counter is clocked when A12 rises after being low for long
+    /// enough, which happens once per scanline when the background and sprites use
+    /// different pattern tables.
+    pub fn set_a12<I: Interrupts>(&mut self, high: bool, ppu_cycle: u64, interrupts: &mut I) {
+        match (self.a12_high, high) {
+            (true, false) => self.a12_fell_at = ppu_cycle,
+            (false, true) if ppu_cycle.wrapping_sub(self.a12_fell_at) >= A12_LOW_PPU_CYCLES => {
+                self.clock_irq_counter(interrupts)
+            }
+            _ => {}
+        }
+
+        self.a12_high = high;
+    }
+
+    /// Clocks the IRQ counter directly, for PPUs that do not track A12 and instead clock it
+    /// at a fixed point of each rendered scanline. Raises an IRQ when the counter reaches 0
+    /// while IRQs are enabled.
+    pub fn clock_irq_counter<I: Interrupts>(&mut self, interrupts: &mut I) {
+        match self.irq_counter == 0 || self.irq_reload {
+            true => {
+                self.irq_counter = self.irq_latch;
+                self.irq_reload = false;
+            }
+            false => self.irq_counter -= 1,
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+            interrupts.set_interrupt_state(true);
+        }
+    }
+
+    fn prg_offset(&self, address: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let r6 = (self.banks[6] & 0x3F) as usize;
+        let r7 = (self.banks[7] & 0x3F) as usize;
+
+        let bank = match ((address >> 13) & 0b11, self.bank_select & 0x40 != 0) {
+            (0, false) => r6,
+            (0, true) => banks - 2,
+            (1, _) => r7,
+            (2, false) => banks - 2,
+            (2, true) => r6,
+            _ => banks - 1,
+        };
+
+        (bank % banks) * PRG_BANK_SIZE + (address as usize & 0x1FFF)
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8) {
+        match address & 0xE001 {
+            0x8000 => self.bank_select = byte,
+            0x8001 => self.banks[(self.bank_select & 0x07) as usize] = byte,
+            0xA000 => self.mirroring = byte,
+            0xA001 => self.prg_ram_protect = byte,
+            0xC000 => self.irq_latch = byte,
+            0xC001 => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000 => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+}
+
+impl Cartridge for Mmc3 {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF if self.prg_ram_protect & 0x80 != 0 => self.prg_ram.read(address),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            // bit 6 protects the PRG-RAM from writes
+            0x6000..=0x7FFF if self.prg_ram_protect & 0xC0 == 0x80 => {
+                self.prg_ram.write(address, byte)
+            }
+            0x8000..=0xFFFF => self.write_register(address, byte),
+            _ => {}
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let flags = self.irq_reload as u8
+            | (self.irq_enabled as u8) << 1
+            | (self.irq_pending as u8) << 2
+            | (self.a12_high as u8) << 3;
+
+        self.prg_ram.save_state(writer)?;
+        writer.write_all(&[self.bank_select])?;
+        writer.write_all(&self.banks)?;
+        writer.write_all(&[
+            self.mirroring,
+            self.prg_ram_protect,
+            self.irq_latch,
+            self.irq_counter,
+            flags,
+        ])?;
+        writer.write_all(&self.a12_fell_at.to_le_bytes())
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut bank_select = [0];
+        let mut banks = [0; 8];
+        let mut registers = [0; 5];
+        let mut a12_fell_at = [0; 8];
+
+        let prg_ram = self.prg_ram.read_state(reader)?;
+        reader.read_exact(&mut bank_select)?;
+        reader.read_exact(&mut banks)?;
+        reader.read_exact(&mut registers)?;
+        reader.read_exact(&mut a12_fell_at)?;
+
+        let flags;
+        self.prg_ram.bytes = prg_ram;
+        self.bank_select = bank_select[0];
+        self.banks = banks;
+        [
+            self.mirroring,
+            self.prg_ram_protect,
+            self.irq_latch,
+            self.irq_counter,
+            flags,
+        ] = registers;
+        self.irq_reload = flags & 1 != 0;
+        self.irq_enabled = flags & 0b10 != 0;
+        self.irq_pending = flags & 0b100 != 0;
+        self.a12_high = flags & 0b1000 != 0;
+        self.a12_fell_at = u64::from_le_bytes(a12_fell_at);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::NesBus;
+    use crate::mappers::test::rom;
+    use crate::test_util::InterruptsContainer;
+    use crate::Cpu;
+
+    /// Raises A12 at the start of each of `count` scanlines, with a short drop in the middle
+    /// like the ones between sprite fetches.
+    fn scanlines(mmc3: &mut Mmc3, irq: &mut InterruptsContainer, first: u64, count: u64) {
+        for scanline in first..first + count {
+            let start = scanline * 341;
+            mmc3.set_a12(false, start, irq);
+            mmc3.set_a12(true, start + 260, irq);
+            mmc3.set_a12(false, start + 264, irq);
+            mmc3.set_a12(true, start + 268, irq);
+        }
+    }
+
+    #[test]
+    fn test_mmc3_banking() {
+        let mut mmc3 = Mmc3::new(&rom(4, 0x20000, 0x20000)).unwrap();
+        assert_eq!(mmc3.read(0xC000), Some(14));
+        assert_eq!(mmc3.read(0xE000), Some(15));
+
+        for (register, bank) in [(6, 3), (7, 5), (0, 9), (5, 20)] {
+            mmc3.write(0x8000, register);
+            mmc3.write(0x8001, bank);
+        }
+        assert_eq!(mmc3.read(0x8000), Some(3));
+        assert_eq!(mmc3.read(0xA000), Some(5));
+        assert_eq!(mmc3.chr_offset(0x0400), 0x2400);
+        assert_eq!(mmc3.chr_offset(0x1C00), 20 * 0x400);
+
+        // PRG mode 1 and CHR inversion
+        mmc3.write(0x8000, 0xC0);
+        assert_eq!(mmc3.read(0x8000), Some(14));
+        assert_eq!(mmc3.read(0xC000), Some(3));
+        assert_eq!(mmc3.chr_offset(0x1000), 0x2000);
+        assert_eq!(mmc3.chr_offset(0x0C00), 20 * 0x400);
+
+        mmc3.write(0xA000, 1);
+        assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+
+        // write protected PRG-RAM can still be read
+        mmc3.write(0x6000, 0x42);
+        mmc3.write(0xA001, 0xC0);
+        mmc3.write(0x6000, 0x24);
+        assert_eq!(mmc3.read(0x6000), Some(0x42));
+        mmc3.write(0xA001, 0x00);
+        assert_eq!(mmc3.read(0x6000), None);
+    }
+
+    #[test]
+    fn test_mmc3_irq() {
+        let mut mmc3 = Mmc3::new(&rom(4, 0x8000, 0x2000)).unwrap();
+        let mut irq = InterruptsContainer::default();
+
+        mmc3.write(0xC000, 3);
+        mmc3.write(0xC001, 0);
+        mmc3.write(0xE001, 0);
+
+        // the first scanline reloads the counter
+        scanlines(&mut mmc3, &mut irq, 0, 3);
+        assert!(!irq.interrupt);
+        scanlines(&mut mmc3, &mut irq, 3, 1);
+        assert!(irq.interrupt);
+        assert!(mmc3.irq_pending());
+
+        // the cpu clears the line when it services the IRQ, and the handler acknowledges it
+        irq.interrupt = false;
+        mmc3.write(0xE000, 0);
+        assert!(!mmc3.irq_pending());
+
+        scanlines(&mut mmc3, &mut irq, 4, 4);
+        assert!(!irq.interrupt);
+
+        // the counter reloads after reaching 0
+        mmc3.write(0xE001, 0);
+        scanlines(&mut mmc3, &mut irq, 8, 3);
+        assert!(!irq.interrupt);
+        scanlines(&mut mmc3, &mut irq, 11, 1);
+        assert!(irq.interrupt);
+    }
+
+    #[test]
+    fn test_mmc3_acknowledge() {
+        // in the last bank, which is fixed at $E000
+        let program = [
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x00, 0xC0, // STA $C000
+            0x8D, 0x01, 0xC0, // STA $C001
+            0x8D, 0x01, 0xE0, // STA $E001
+            0x8D, 0x00, 0xE0, // STA $E000
+            0x58, // CLI
+            0xEA, // NOP
+        ];
+
+        let mut rom = rom(4, 0x8000, 0x2000);
+        rom.prg_rom[0x6000..0x6000 + program.len()].copy_from_slice(&program);
+        // reset to $E000 and IRQ to $E100
+        rom.prg_rom[0x7FFC..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE1]);
+
+        let bus = NesBus::with_cartridge(Mmc3::new(&rom).unwrap());
+        let mut cpu = Cpu::new(bus, InterruptsContainer::default());
+        cpu.initialize();
+
+        let step = |cpu: &mut Cpu<NesBus<_, _, _, Mmc3>, InterruptsContainer>| {
+            cpu.cycle();
+            cpu.memory_mapper.update_irq(&mut cpu.interrupts);
+        };
+
+        for _ in 0..4 {
+            step(&mut cpu);
+        }
+
+        // a latch of 0 raises the IRQ on the next clock, while interrupts are disabled
+        cpu.memory_mapper
+            .cartridge
+            .clock_irq_counter(&mut cpu.interrupts);
+        cpu.memory_mapper.update_irq(&mut cpu.interrupts);
+        assert!(cpu.interrupts.interrupt);
+
+        // the acknowledge releases the line, so nothing is serviced after CLI
+        step(&mut cpu);
+        assert!(!cpu.interrupts.interrupt);
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.program_counter, 0xE010);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_mmc3_truncated_state() {
+        let mut mmc3 = Mmc3::new(&rom(4, 0x20000, 0x2000)).unwrap();
+        mmc3.write(0x6000, 0x42);
+        let mut state = Vec::new();
+        mmc3.save_state(&mut state).unwrap();
+
+        // R6 selects the bank at $8000
+        mmc3.write(0x6000, 0x24);
+        mmc3.write(0x8000, 6);
+        mmc3.write(0x8001, 3);
+        assert!(mmc3.load_state(&mut &state[..state.len() - 1]).is_err());
+        assert_eq!(mmc3.read(0x6000), Some(0x24));
+        assert_eq!(mmc3.read(0x8000), Some(3));
+    }
+}
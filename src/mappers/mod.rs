@@ -0,0 +1,167 @@
+//! The cpu side of common cartridge mappers, for use as the cartridge of a
+//! [`NesBus`](crate::bus::NesBus).
+//!
+//! Each mapper handles PRG-ROM banking, its registers and its PRG-RAM. The PPU side is left
+//! to the user, who can ask a mapper for its current [`Mirroring`](crate::rom::Mirroring) and for the offset into
+//! CHR memory of a PPU address with `mirroring` and `chr_offset`.
+
+use crate::bus::Cartridge;
+use crate::rom::{Nrom, Rom, RomError};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+mod axrom;
+mod cnrom;
+mod mmc1;
+mod mmc3;
+mod uxrom;
+
+pub use axrom::Axrom;
+pub use cnrom::Cnrom;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use uxrom::Uxrom;
+
+/// The submapper of UxROM, CNROM and AxROM boards whose register writes are ANDed with the
+/// PRG-ROM byte at the same address.
+const BUS_CONFLICTS_SUBMAPPER: u8 = 2;
+
+/// Creates the cartridge for the mapper of a ROM.
+pub fn from_rom(rom: &Rom) -> Result<Box<dyn Cartridge>, RomError> {
+    Ok(match rom.mapper {
+        0 => Box::new(Nrom::new(rom)?),
+        1 => Box::new(Mmc1::new(rom)?),
+        2 => Box::new(Uxrom::new(rom)?),
+        3 => Box::new(Cnrom::new(rom)?),
+        4 => Box::new(Mmc3::new(rom)?),
+        7 => Box::new(Axrom::new(rom)?),
+        mapper => return Err(RomError::UnsupportedMapper(mapper)),
+    })
+}
+
+/// Checks the mapper number of a ROM and that its PRG-ROM is a whole number of banks.
+fn check_rom(rom: &Rom, mapper: u16, bank_size: usize) -> Result<(), RomError> {
+    if rom.mapper != mapper {
+        return Err(RomError::UnsupportedMapper(rom.mapper));
+    }
+
+    match rom.prg_rom.len() {
+        0 => Err(RomError::InvalidPrgRomSize(0)),
+        size if !size.is_multiple_of(bank_size) => Err(RomError::InvalidPrgRomSize(size)),
+        _ => Ok(()),
+    }
+}
+
+/// The size of the CHR memory, which is 8KB of CHR-RAM when there is no CHR-ROM and the
+/// header does not say otherwise.
+fn chr_size(rom: &Rom) -> usize {
+    match (rom.chr_rom.len(), rom.chr_ram_size + rom.chr_nvram_size) {
+        (0, 0) => 0x2000,
+        (0, ram_size) => ram_size,
+        (rom_size, _) => rom_size,
+    }
+}
+
+/// The PRG-RAM at $6000-$7FFF, mirrored when smaller than 8KB.
+#[derive(Clone, Debug)]
+struct PrgRam {
+    bytes: Vec<u8>,
+    battery: bool,
+}
+
+impl PrgRam {
+    fn new(rom: &Rom) -> Self {
+        Self {
+            bytes: vec![0; rom.prg_ram_size + rom.prg_nvram_size],
+            battery: rom.battery,
+        }
+    }
+
+    fn read(&self, address: u16) -> Option<u8> {
+        match self.bytes.is_empty() {
+            true => None,
+            false => Some(self.bytes[(address as usize - 0x6000) % self.bytes.len()]),
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        if !self.bytes.is_empty() {
+            let len = self.bytes.len();
+            self.bytes[(address as usize - 0x6000) % len] = byte;
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        (self.battery && !self.bytes.is_empty()).then_some(&self.bytes[..])
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        (self.battery && !self.bytes.is_empty()).then_some(&mut self.bytes[..])
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.bytes)
+    }
+
+    /// Reads what was written by [`Self::save_state`] into a new buffer, which the mapper
+    /// assigns to `bytes` once the rest of its state has been read.
+    #[cfg(feature = "std")]
+    fn read_state(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; self.bytes.len()];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// The offset into the PRG-ROM of bank `bank`, wrapping around when there are fewer banks.
+fn bank_offset(bank: usize, bank_size: usize, rom_size: usize) -> usize {
+    (bank * bank_size) % rom_size
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::{ConsoleType, HeaderFormat, Mirroring, Timing};
+
+    /// A ROM whose PRG-ROM bytes are the number of the 8KB bank they are in, and whose
+    /// CHR-ROM bytes are the number of their 1KB bank.
+    pub(super) fn rom(mapper: u16, prg_size: usize, chr_size: usize) -> Rom {
+        Rom {
+            format: HeaderFormat::INes,
+            mapper,
+            submapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: true,
+            timing: Timing::Ntsc,
+            console_type: ConsoleType::Nes,
+            trainer: None,
+            prg_rom: (0..prg_size).map(|i| (i / 0x2000) as u8).collect(),
+            chr_rom: (0..chr_size).map(|i| (i / 0x400) as u8).collect(),
+            prg_ram_size: 0,
+            prg_nvram_size: 0x2000,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_from_rom() {
+        let mut cartridge = from_rom(&rom(2, 0x8000, 0)).unwrap();
+        cartridge.write(0x6000, 0x42);
+        assert_eq!(cartridge.battery_ram().unwrap()[0], 0x42);
+        assert_eq!(cartridge.read(0x8000), Some(0));
+
+        assert_eq!(
+            from_rom(&rom(5, 0x8000, 0)).err(),
+            Some(RomError::UnsupportedMapper(5))
+        );
+        assert_eq!(
+            from_rom(&rom(1, 0x3000, 0)).err(),
+            Some(RomError::InvalidPrgRomSize(0x3000))
+        );
+    }
+}
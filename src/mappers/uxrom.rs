@@ -0,0 +1,131 @@
+use super::{bank_offset, check_rom, PrgRam, BUS_CONFLICTS_SUBMAPPER};
+use crate::bus::Cartridge;
+use crate::rom::{Mirroring, Rom, RomError};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+/// UxROM (mapper 2), which switches the 16KB bank at $8000.
+///
+/// | Address       | Contents                                |
+/// |---------------|-----------------------------------------|
+/// | $6000 - $7FFF | PRG-RAM, if the header has any          |
+/// | $8000 - $BFFF | a switchable 16KB PRG-ROM bank          |
+/// | $C000 - $FFFF | the last 16KB PRG-ROM bank              |
+///
+/// Writing to $8000-$FFFF selects the bank. CHR is 8KB and not banked.
+#[derive(Clone, Debug)]
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        check_rom(rom, 2, PRG_BANK_SIZE)?;
+
+        Ok(Self {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram: PrgRam::new(rom),
+            mirroring: rom.mirroring,
+            bus_conflicts: rom.submapper == BUS_CONFLICTS_SUBMAPPER,
+            bank: 0,
+        })
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    /// Returns the offset into CHR memory of a PPU address in $0000-$1FFF.
+    pub fn chr_offset(&self, address: u16) -> usize {
+        address as usize & 0x1FFF
+    }
+
+    fn prg_offset(&self, address: u16) -> usize {
+        let bank = match address {
+            0x8000..=0xBFFF => self.bank as usize,
+            _ => self.prg_rom.len() / PRG_BANK_SIZE - 1,
+        };
+
+        bank_offset(bank, PRG_BANK_SIZE, self.prg_rom.len()) + (address as usize & 0x3FFF)
+    }
+}
+
+impl Cartridge for Uxrom {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.read(address),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => self.prg_ram.write(address, byte),
+            0x8000..=0xFFFF => {
+                self.bank = match self.bus_conflicts {
+                    true => byte & self.prg_rom[self.prg_offset(address)],
+                    false => byte,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_ram_mut()
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.prg_ram.save_state(writer)?;
+        writer.write_all(&[self.bank])
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut bank = [0];
+        let prg_ram = self.prg_ram.read_state(reader)?;
+        reader.read_exact(&mut bank)?;
+        self.prg_ram.bytes = prg_ram;
+        self.bank = bank[0];
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mappers::test::rom;
+
+    #[test]
+    fn test_uxrom() {
+        let mut uxrom = Uxrom::new(&rom(2, 0x20000, 0)).unwrap();
+        assert_eq!(uxrom.read(0x8000), Some(0));
+        assert_eq!(uxrom.read(0xC000), Some(14));
+        assert_eq!(uxrom.read(0xFFFF), Some(15));
+
+        uxrom.write(0x8000, 3);
+        assert_eq!(uxrom.read(0x8000), Some(6));
+        assert_eq!(uxrom.read(0xBFFF), Some(7));
+        assert_eq!(uxrom.read(0xC000), Some(14));
+
+        // the ROM byte at $C003 is 14, so only bit 1 and 2 survive the conflict
+        let mut conflicting = rom(2, 0x20000, 0);
+        conflicting.submapper = BUS_CONFLICTS_SUBMAPPER;
+        let mut uxrom = Uxrom::new(&conflicting).unwrap();
+        uxrom.write(0xC003, 7);
+        assert_eq!(uxrom.read(0x8000), Some(12));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::Instruction;
+
+/// Describes which kind of interrupt was serviced.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum InterruptKind {
+    Reset,
+    Irq,
+    Nmi,
+}
+
+/// Hooks into everything the cpu does, which is what tracers, profilers, code/data
+/// loggers and watchpoints are built on. Every method defaults to doing nothing, so
+/// only the hooks that are needed have to be implemented.
+///
+/// The cpu is generic over its observer and defaults to [`NoObserver`], so the hooks
+/// compile away entirely when they are not used. If the observer needs to be swapped at
+/// runtime, `&mut dyn Observer` can be used as the observer type.
+///
+/// # Examples
+/// ### Counting the instructions ran.
+/// ```
+/// use nes6502::{Instruction, Observer};
+///
+/// #[derive(Default)]
+/// struct InstructionCounter(u64);
+///
+/// impl Observer for InstructionCounter {
+///     fn on_instruction(&mut self, _address: u16, _instruction: &Instruction) {
+///         self.0 += 1;
+///     }
+/// }
+/// ```
+pub trait Observer {
+    /// Called after an instruction is fetched, but before it is executed. `address`
+    /// is the address of the opcode.
+    #[inline(always)]
+    fn on_instruction(&mut self, address: u16, instruction: &Instruction) {
+        let _ = (address, instruction);
+    }
+
+    /// Called for every byte the cpu reads while executing, including instruction fetches.
+    /// Reads made with [`crate::Cpu::read`] and [`crate::Cpu::peek`] are not reported.
+    #[inline(always)]
+    fn on_read(&mut self, address: u16, value: u8) {
+        let _ = (address, value);
+    }
+
+    /// Called for every byte written to the memory mapper.
+    #[inline(always)]
+    fn on_write(&mut self, address: u16, value: u8) {
+        let _ = (address, value);
+    }
+
+    /// Called when an interrupt or reset is serviced, before its vector is read.
+    #[inline(always)]
+    fn on_interrupt(&mut self, kind: InterruptKind) {
+        let _ = kind;
+    }
+}
+
+/// The default observer, which ignores everything.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+impl<T: Observer + ?Sized> Observer for &mut T {
+    #[inline(always)]
+    fn on_instruction(&mut self, address: u16, instruction: &Instruction) {
+        (**self).on_instruction(address, instruction)
+    }
+
+    #[inline(always)]
+    fn on_read(&mut self, address: u16, value: u8) {
+        (**self).on_read(address, value)
+    }
+
+    #[inline(always)]
+    fn on_write(&mut self, address: u16, value: u8) {
+        (**self).on_write(address, value)
+    }
+
+    #[inline(always)]
+    fn on_interrupt(&mut self, kind: InterruptKind) {
+        (**self).on_interrupt(kind)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{Memory, NoInterrupts};
+    use crate::Cpu;
+
+    #[derive(Default)]
+    struct Recorder {
+        instructions: Vec<u16>,
+        reads: Vec<(u16, u8)>,
+        writes: Vec<(u16, u8)>,
+        interrupts: Vec<InterruptKind>,
+    }
+
+    impl Observer for Recorder {
+        fn on_instruction(&mut self, address: u16, _instruction: &Instruction) {
+            self.instructions.push(address);
+        }
+
+        fn on_read(&mut self, address: u16, value: u8) {
+            self.reads.push((address, value));
+        }
+
+        fn on_write(&mut self, address: u16, value: u8) {
+            self.writes.push((address, value));
+        }
+
+        fn on_interrupt(&mut self, kind: InterruptKind) {
+            self.interrupts.push(kind);
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        // LDA $10; STA $11
+        let mut memory = Memory::with_program(0x8000, &[0xA5, 0x10, 0x85, 0x11]);
+        memory.0[0x0010] = 0x42;
+
+        let mut recorder = Recorder::default();
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_observer(&mut recorder);
+        cpu.initialize();
+        cpu.cycle();
+        cpu.cycle();
+
+        assert_eq!(recorder.interrupts, vec![InterruptKind::Reset]);
+        assert_eq!(recorder.instructions, vec![0x8000, 0x8002]);
+        assert_eq!(
+            recorder.reads[2..],
+            [
+                (0x8000, 0xA5),
+                (0x8001, 0x10),
+                (0x0010, 0x42),
+                (0x8002, 0x85),
+                (0x8003, 0x11)
+            ]
+        );
+        assert_eq!(recorder.writes[3..], [(0x0011, 0x42)]);
+    }
+}
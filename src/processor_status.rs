@@ -4,6 +4,7 @@
 /// and getting. We do this as we may have to call these nearly every cpu cycle and
 /// we dont want to have to do another check or copy another byte.
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProcessorStatus(pub u8);
 
 impl ProcessorStatus {
@@ -217,4 +218,14 @@ mod test {
         flag_reg.clear_negative_flag();
         assert!(!flag_reg.negative_flag());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let flag_reg = ProcessorStatus(0b1010_0101);
+
+        let json = sonic_rs::to_string(&flag_reg).unwrap();
+        assert_eq!(json, "165");
+        assert_eq!(sonic_rs::from_str::<ProcessorStatus>(&json).unwrap().0, 0b1010_0101);
+    }
 }
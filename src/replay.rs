@@ -0,0 +1,563 @@
+//! Recording the controller input of each frame and replaying it deterministically, with
+//! checksums of the cpu state to catch desyncs. Movies can be imported from and exported
+//! to the FM2 text format of FCEUX, so TAS movies can be used as regression tests.
+//!
+//! There is no PPU to signal the end of a frame, so frames are measured with the cpu cycle
+//! counter, using the frame length of the movie's [`Timing`].
+
+use crate::bus::{ApuPort, Cartridge, NesBus, PpuPort};
+use crate::controller::{Buttons, Joypads};
+use crate::rom::Timing;
+use crate::{Cpu, Interrupts, Observer, SavestateError};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+/// A soft reset, pressed at the start of a frame.
+pub const COMMAND_RESET: u8 = 0b01;
+/// A power cycle. Only the cpu can be reset, so it is replayed like [`COMMAND_RESET`].
+pub const COMMAND_POWER: u8 = 0b10;
+
+/// The order of the buttons in an FM2 input line, from bit 7 to bit 0 of [`Buttons`].
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+/// The prefix of the comments that hold the checksums of a movie.
+const FM2_CHECKSUM_COMMENT: &str = "checksum ";
+
+/// The length of a frame in halves of a cpu cycle, as NTSC and PAL frames are not a whole
+/// number of cycles.
+fn half_cycles_per_frame(timing: Timing) -> u64 {
+    match timing {
+        // 341 * 262 - 0.5 PPU cycles, at 3 per cpu cycle
+        Timing::Ntsc | Timing::MultipleRegion => 59561,
+        // 341 * 312 PPU cycles, at 3.2 per cpu cycle
+        Timing::Pal => 66495,
+        // 341 * 312 PPU cycles, at 3 per cpu cycle
+        Timing::Dendy => 70928,
+    }
+}
+
+/// Returns the cycle count at the end of frame `frame` of a movie started at `start_cycles`.
+fn frame_end(timing: Timing, start_cycles: u64, frame: usize) -> u64 {
+    start_cycles + (frame as u64 + 1) * half_cycles_per_frame(timing) / 2
+}
+
+/// Applies the input of a frame and runs the cpu until `end_cycles`, counting the cycles
+/// stalled by OAM DMAs and driving the IRQ line of the cartridge.
+fn run_frame<P, A, R, I, O>(
+    cpu: &mut Cpu<NesBus<P, A, Joypads, R>, I, O>,
+    frame: &Frame,
+    end_cycles: u64,
+) where
+    P: PpuPort,
+    A: ApuPort,
+    R: Cartridge,
+    I: Interrupts,
+    O: Observer,
+{
+    if frame.commands & (COMMAND_RESET | COMMAND_POWER) != 0 {
+        cpu.reset();
+    }
+
+    for (port, buttons) in frame.buttons.into_iter().enumerate() {
+        cpu.memory_mapper.controller.set_buttons(port, buttons);
+    }
+
+    while cpu.cycles < end_cycles {
+        cpu.cycle();
+        cpu.cycles += cpu.memory_mapper.take_dma_stall() as u64;
+        cpu.memory_mapper.update_irq(&mut cpu.interrupts);
+    }
+}
+
+/// The input of a single frame.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Frame {
+    /// The FM2 commands, such as [`COMMAND_RESET`].
+    pub commands: u8,
+    /// The buttons held on each controller.
+    pub buttons: [Buttons; 2],
+}
+
+/// A recording of the input of each frame.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Movie {
+    pub timing: Timing,
+    pub rom_filename: String,
+    pub rerecord_count: u32,
+    /// The FM2 comments, such as `author <name>`, without the checksums.
+    pub comments: Vec<String>,
+    /// The FM2 header lines that are not used here (such as `guid` and `romChecksum`) as
+    /// (key, value), which are kept so that they are exported unchanged.
+    pub extra_header: Vec<(String, String)>,
+    /// The savestate the movie starts from, or None to start from a freshly initialized
+    /// cpu, which [`Self::replay`] initializes. FM2 cannot hold it, so it has to be kept
+    /// alongside an exported movie.
+    pub start_state: Option<Vec<u8>>,
+    pub frames: Vec<Frame>,
+    /// The checksums of the cpu state at the end of some of the frames as (frame, checksum),
+    /// sorted by frame. See [`CpuState::checksum`](crate::CpuState::checksum).
+    pub checksums: Vec<(usize, u64)>,
+}
+
+impl Movie {
+    /// Replays the movie, first loading [`Self::start_state`] if there is one or initializing
+    /// the cpu otherwise. Stops at the first checksum that does not match.
+    pub fn replay<P, A, R, I, O>(
+        &self,
+        cpu: &mut Cpu<NesBus<P, A, Joypads, R>, I, O>,
+    ) -> Result<(), ReplayError>
+    where
+        P: PpuPort,
+        A: ApuPort,
+        R: Cartridge,
+        I: Interrupts,
+        O: Observer,
+    {
+        match &self.start_state {
+            Some(start_state) => cpu.load_state(&mut start_state.as_slice())?,
+            None => cpu.initialize(),
+        }
+
+        let start_cycles = cpu.cycles;
+        let mut checksums = self.checksums.iter().peekable();
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            run_frame(cpu, frame, frame_end(self.timing, start_cycles, index));
+
+            while let Some(&(_, expected)) = checksums.next_if(|(frame, _)| *frame <= index) {
+                let actual = cpu.state().checksum();
+                if actual != expected {
+                    return Err(ReplayError::Desync {
+                        frame: index,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a movie in the FM2 text format. The checksums are read from comments of the
+    /// form `comment checksum <frame> <hex>`, which is how [`Self::write_fm2`] stores them.
+    /// Movies that start from an FCEUX savestate are rejected, as it cannot be loaded here.
+    pub fn from_fm2(text: &str) -> Result<Self, Fm2Error> {
+        let mut movie = Movie::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let invalid_line = Fm2Error::InvalidLine(index + 1);
+
+            if line.starts_with('|') {
+                movie
+                    .frames
+                    .push(parse_fm2_frame(line).ok_or(invalid_line)?);
+                continue;
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if value != "3" => return Err(Fm2Error::UnsupportedVersion),
+                "binary" if value != "0" => return Err(Fm2Error::Binary),
+                "savestate" => return Err(Fm2Error::Savestate),
+                "fourscore" | "port2" if value != "0" => return Err(Fm2Error::UnsupportedPorts),
+                "port0" | "port1" if value != "0" && value != "1" => {
+                    return Err(Fm2Error::UnsupportedPorts)
+                }
+                // written by every export
+                "version" | "binary" | "fourscore" | "port0" | "port1" | "port2" => {}
+                "palFlag" => {
+                    movie.timing = match value == "1" {
+                        true => Timing::Pal,
+                        false => Timing::Ntsc,
+                    }
+                }
+                "rerecordCount" => {
+                    movie.rerecord_count = value.parse().map_err(|_| invalid_line)?
+                }
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "comment" => match value.strip_prefix(FM2_CHECKSUM_COMMENT) {
+                    Some(checksum) => movie
+                        .checksums
+                        .push(parse_fm2_checksum(checksum).ok_or(invalid_line)?),
+                    None => movie.comments.push(value.to_string()),
+                },
+                _ => movie
+                    .extra_header
+                    .push((key.to_string(), value.to_string())),
+            }
+        }
+
+        movie.checksums.sort_by_key(|&(frame, _)| frame);
+        Ok(movie)
+    }
+
+    /// Writes the movie in the FM2 text format, with a standard controller in both ports.
+    /// The checksums are written as comments, while [`Self::start_state`] is left out.
+    pub fn write_fm2(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "version 3")?;
+        for (key, value) in &self.extra_header {
+            writeln!(writer, "{} {}", key, value)?;
+        }
+
+        writeln!(writer, "rerecordCount {}", self.rerecord_count)?;
+        writeln!(writer, "palFlag {}", (self.timing == Timing::Pal) as u8)?;
+        writeln!(writer, "romFilename {}", self.rom_filename)?;
+        writeln!(writer, "fourscore 0")?;
+        writeln!(writer, "port0 1")?;
+        writeln!(writer, "port1 1")?;
+        writeln!(writer, "port2 0")?;
+
+        for comment in &self.comments {
+            writeln!(writer, "comment {}", comment)?;
+        }
+        for (frame, checksum) in &self.checksums {
+            writeln!(
+                writer,
+                "comment {}{} {:016x}",
+                FM2_CHECKSUM_COMMENT, frame, checksum
+            )?;
+        }
+
+        for frame in &self.frames {
+            let [port0, port1] = frame.buttons.map(format_fm2_buttons);
+            writeln!(writer, "|{}|{}|{}||", frame.commands, port0, port1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses an input line of the form `|commands|RLDUTSBA|RLDUTSBA|port2|`.
+fn parse_fm2_frame(line: &str) -> Option<Frame> {
+    let mut fields = line.split('|').skip(1);
+    let commands = fields.next()?.trim().parse().ok()?;
+
+    let mut buttons = [Buttons::NONE; 2];
+    for port in &mut buttons {
+        *port = parse_fm2_buttons(fields.next()?)?;
+    }
+
+    Some(Frame { commands, buttons })
+}
+
+/// Parses the buttons of a port, which are a letter when held and a `.` or space when not.
+/// The field is empty when nothing is plugged into the port.
+fn parse_fm2_buttons(field: &str) -> Option<Buttons> {
+    match field.len() {
+        0 => Some(Buttons::NONE),
+        8 => Some(Buttons(field.bytes().fold(0, |bits, button| {
+            (bits << 1) | (button != b'.' && button != b' ') as u8
+        }))),
+        _ => None,
+    }
+}
+
+fn format_fm2_buttons(buttons: Buttons) -> String {
+    FM2_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(i, &button)| match buttons.0 & (0x80 >> i) != 0 {
+            true => button as char,
+            false => '.',
+        })
+        .collect()
+}
+
+/// Parses `<frame> <hex>`.
+fn parse_fm2_checksum(checksum: &str) -> Option<(usize, u64)> {
+    let (frame, checksum) = checksum.split_once(' ')?;
+    Some((frame.parse().ok()?, u64::from_str_radix(checksum, 16).ok()?))
+}
+
+/// Records a movie while running the cpu one frame at a time.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    movie: Movie,
+    start_cycles: u64,
+    checksum_interval: usize,
+}
+
+impl Recorder {
+    /// Starts recording from the current state of the cpu, which is saved as the start
+    /// state of the movie. A checksum is recorded every `checksum_interval` frames, or
+    /// never if it is 0.
+    pub fn new<P, A, R, I, O>(
+        cpu: &Cpu<NesBus<P, A, Joypads, R>, I, O>,
+        timing: Timing,
+        checksum_interval: usize,
+    ) -> io::Result<Self>
+    where
+        P: PpuPort,
+        A: ApuPort,
+        R: Cartridge,
+        I: Interrupts,
+        O: Observer,
+    {
+        let mut start_state = Vec::new();
+        cpu.save_state(&mut start_state)?;
+
+        Ok(Self {
+            movie: Movie {
+                timing,
+                start_state: Some(start_state),
+                ..Default::default()
+            },
+            start_cycles: cpu.cycles,
+            checksum_interval,
+        })
+    }
+
+    /// Runs a frame with the given input and adds it to the movie.
+    pub fn record_frame<P, A, R, I, O>(
+        &mut self,
+        cpu: &mut Cpu<NesBus<P, A, Joypads, R>, I, O>,
+        frame: Frame,
+    ) where
+        P: PpuPort,
+        A: ApuPort,
+        R: Cartridge,
+        I: Interrupts,
+        O: Observer,
+    {
+        let index = self.movie.frames.len();
+        run_frame(
+            cpu,
+            &frame,
+            frame_end(self.movie.timing, self.start_cycles, index),
+        );
+        self.movie.frames.push(frame);
+
+        if self.checksum_interval != 0 && (index + 1).is_multiple_of(self.checksum_interval) {
+            self.movie.checksums.push((index, cpu.state().checksum()));
+        }
+    }
+
+    /// Returns the recorded movie.
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Why a replay stopped.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The checksum of the cpu state at the end of `frame` differs from the recorded one.
+    Desync {
+        frame: usize,
+        expected: u64,
+        actual: u64,
+    },
+    /// The start state could not be loaded.
+    StartState(SavestateError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Desync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "desync at frame {}, expected checksum {:016x} but found {:016x}",
+                frame, expected, actual
+            ),
+            ReplayError::StartState(e) => write!(f, "could not load the start state: {}", e),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::StartState(e) => Some(e),
+            ReplayError::Desync { .. } => None,
+        }
+    }
+}
+
+impl From<SavestateError> for ReplayError {
+    fn from(e: SavestateError) -> Self {
+        ReplayError::StartState(e)
+    }
+}
+
+/// Why an FM2 movie could not be imported.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Fm2Error {
+    /// The movie is not version 3, the only one FCEUX writes.
+    UnsupportedVersion,
+    /// The input is in the binary form of FM2.
+    Binary,
+    /// The movie uses a Four Score, the expansion port or a device other than a standard
+    /// controller.
+    UnsupportedPorts,
+    /// The movie starts from an FCEUX savestate instead of a power on.
+    Savestate,
+    /// A line could not be parsed, with its line number starting from 1.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for Fm2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fm2Error::UnsupportedVersion => write!(f, "unsupported FM2 version"),
+            Fm2Error::Binary => write!(f, "binary FM2 movies are not supported"),
+            Fm2Error::UnsupportedPorts => write!(f, "only standard controllers are supported"),
+            Fm2Error::Savestate => write!(f, "movies starting from a savestate are not supported"),
+            Fm2Error::InvalidLine(line) => write!(f, "invalid line {}", line),
+        }
+    }
+}
+
+impl Error for Fm2Error {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Unconnected;
+    use crate::rom::{Nrom, Rom, INES_MAGIC};
+    use crate::test_util::NoInterrupts;
+
+    type TestCpu = Cpu<NesBus<Unconnected, Unconnected, Joypads, Nrom>, NoInterrupts>;
+
+    /// A cpu running a program that keeps polling the first controller into $10 and adding
+    /// it to $11.
+    fn cpu() -> TestCpu {
+        let program = [
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA2, 0x08, // LDX #$08
+            0xAD, 0x16, 0x40, // LDA $4016
+            0x4A, // LSR A
+            0x26, 0x10, // ROL $10
+            0xCA, // DEX
+            0xD0, 0xF7, // BNE $C00C
+            0xA5, 0x10, // LDA $10
+            0x65, 0x11, // ADC $11
+            0x85, 0x11, // STA $11
+            0x4C, 0x00, 0xC0, // JMP $C000
+        ];
+
+        let mut bytes = vec![0; 16 + 0x4000];
+        bytes[0..4].copy_from_slice(&INES_MAGIC);
+        bytes[4] = 1;
+        bytes[16..16 + program.len()].copy_from_slice(&program);
+        bytes[16 + 0x3FFD] = 0xC0;
+
+        let cartridge = Nrom::new(&Rom::from_ines(&bytes).unwrap()).unwrap();
+        let bus = NesBus::new(Unconnected, Unconnected, Joypads::new(), cartridge);
+        Cpu::new(bus, NoInterrupts)
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut cpu = cpu();
+        cpu.initialize();
+        let start_cycles = cpu.cycles;
+
+        let mut recorder = Recorder::new(&cpu, Timing::Ntsc, 5).unwrap();
+        for i in 0..20 {
+            let buttons = Buttons(i * 13);
+            recorder.record_frame(
+                &mut cpu,
+                Frame {
+                    commands: 0,
+                    buttons: [buttons, Buttons::NONE],
+                },
+            );
+        }
+
+        let mut movie = recorder.finish();
+        assert_eq!(movie.checksums.len(), 4);
+        assert_eq!(movie.checksums[3].0, 19);
+        // the last instruction of a frame may run past its end
+        let end_cycles = frame_end(Timing::Ntsc, start_cycles, 19);
+        assert!((end_cycles..end_cycles + 7).contains(&cpu.cycles));
+
+        movie.replay(&mut self::cpu()).unwrap();
+
+        // a different input desyncs at the next checksum
+        movie.frames[12].buttons[0] = Buttons::START;
+        match movie.replay(&mut self::cpu()) {
+            Err(ReplayError::Desync { frame, .. }) => assert_eq!(frame, 14),
+            other => panic!("expected a desync, got {:?}", other),
+        }
+
+        // the recording started right after a power on, so it replays without its savestate
+        movie.frames[12].buttons[0] = Buttons(12 * 13);
+        movie.start_state = None;
+        movie.replay(&mut self::cpu()).unwrap();
+    }
+
+    #[test]
+    fn test_fm2() {
+        let fm2 = "version 3\n\
+            emuVersion 22020\n\
+            rerecordCount 42\n\
+            palFlag 0\n\
+            romFilename game\n\
+            guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B\n\
+            fourscore 0\n\
+            port0 1\n\
+            port1 1\n\
+            port2 0\n\
+            comment author someone\n\
+            |1|........|........||\n\
+            |0|R..U...A|.L..T.B.||\n\
+            |0|RLDUTSBA|        ||\n";
+
+        let movie = Movie::from_fm2(fm2).unwrap();
+        assert_eq!(movie.rerecord_count, 42);
+        assert_eq!(movie.rom_filename, "game");
+        assert_eq!(movie.comments, ["author someone"]);
+        assert_eq!(movie.extra_header.len(), 2);
+        assert_eq!(movie.frames.len(), 3);
+        assert_eq!(movie.frames[0].commands, COMMAND_RESET);
+        assert_eq!(
+            movie.frames[1].buttons,
+            [
+                Buttons::RIGHT | Buttons::UP | Buttons::A,
+                Buttons::LEFT | Buttons::START | Buttons::B
+            ]
+        );
+        assert_eq!(movie.frames[2].buttons, [Buttons(0xFF), Buttons::NONE]);
+
+        let mut exported = Movie {
+            checksums: vec![(2, 0x0123_4567_89AB_CDEF)],
+            ..movie
+        };
+        let mut bytes = Vec::new();
+        exported.write_fm2(&mut bytes).unwrap();
+        assert_eq!(
+            Movie::from_fm2(&String::from_utf8(bytes).unwrap()),
+            Ok(exported.clone())
+        );
+
+        exported.timing = Timing::Pal;
+        let mut bytes = Vec::new();
+        exported.write_fm2(&mut bytes).unwrap();
+        assert_eq!(
+            Movie::from_fm2(&String::from_utf8(bytes).unwrap()),
+            Ok(exported)
+        );
+
+        assert_eq!(
+            Movie::from_fm2("fourscore 1\n"),
+            Err(Fm2Error::UnsupportedPorts)
+        );
+        assert_eq!(
+            Movie::from_fm2("version 3\nsavestate base64:AAAA\n"),
+            Err(Fm2Error::Savestate)
+        );
+        assert_eq!(Movie::from_fm2("|0|ABC|\n"), Err(Fm2Error::InvalidLine(1)));
+    }
+}
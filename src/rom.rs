@@ -0,0 +1,521 @@
+//! Loading cartridges from iNES and NES 2.0 files.
+
+use crate::Mapper;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// The bytes every iNES file starts with.
+pub const INES_MAGIC: [u8; 4] = *b"NES\x1A";
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_ROM_BANK_SIZE: usize = 0x4000;
+const CHR_ROM_BANK_SIZE: usize = 0x2000;
+
+/// How the nametables are mirrored.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    /// Every nametable is the first page of VRAM, as selected by some mappers.
+    SingleScreenLower,
+    /// Every nametable is the second page of VRAM.
+    SingleScreenUpper,
+}
+
+/// The version of the header a ROM was loaded from.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum HeaderFormat {
+    INes,
+    Nes2,
+}
+
+/// The CPU/PPU timing a ROM expects. Only NES 2.0 headers store this, so it is
+/// [`Timing::Ntsc`] for iNES files.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Timing {
+    /// The RP2C02, as used in North America and Japan.
+    #[default]
+    Ntsc,
+    /// The RP2C07, as used in Europe.
+    Pal,
+    /// Runs on both NTSC and PAL consoles.
+    MultipleRegion,
+    /// The UA6538, as used in Russia.
+    Dendy,
+}
+
+/// The console a ROM was made for.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum ConsoleType {
+    /// A regular NES or Famicom.
+    #[default]
+    Nes,
+    VsSystem,
+    PlayChoice10,
+    /// One of the NES 2.0 extended console types, such as a VT0x famiclone.
+    Extended(u8),
+}
+
+/// Why a ROM could not be loaded.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum RomError {
+    /// The file does not start with [`INES_MAGIC`].
+    InvalidMagic,
+    /// The file is shorter than its header says it is.
+    Truncated { expected: usize, actual: usize },
+    /// The ROM needs a mapper that is not implemented.
+    UnsupportedMapper(u16),
+    /// The PRG-ROM size is not one the mapper supports.
+    InvalidPrgRomSize(usize),
+    /// A NES 2.0 header specifies a ROM too large to be addressed.
+    SizeOverflow,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::InvalidMagic => write!(f, "not an iNES file"),
+            RomError::Truncated { expected, actual } => write!(
+                f,
+                "file is truncated, expected {} bytes but found {}",
+                expected, actual
+            ),
+            RomError::UnsupportedMapper(mapper) => write!(f, "unsupported mapper {}", mapper),
+            RomError::InvalidPrgRomSize(size) => write!(f, "invalid PRG-ROM size of {} bytes", size),
+            RomError::SizeOverflow => write!(f, "ROM size in the header is too large"),
+        }
+    }
+}
+
+impl Error for RomError {}
+
+/// A cartridge loaded from an iNES or NES 2.0 file.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Rom {
+    pub format: HeaderFormat,
+    pub mapper: u16,
+    /// The variant of the mapper, which is always 0 for iNES files.
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    /// Whether the PRG-RAM is battery backed.
+    pub battery: bool,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    /// The 512 bytes meant to be loaded at $7000, if there are any.
+    pub trainer: Option<Vec<u8>>,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    /// The size of the volatile PRG-RAM in bytes.
+    pub prg_ram_size: usize,
+    /// The size of the battery backed PRG-RAM in bytes.
+    pub prg_nvram_size: usize,
+    /// The size of the volatile CHR-RAM in bytes.
+    pub chr_ram_size: usize,
+    /// The size of the battery backed CHR-RAM in bytes.
+    pub chr_nvram_size: usize,
+}
+
+impl Rom {
+    /// Parses an iNES or NES 2.0 file, depending on the header.
+    pub fn from_ines(bytes: &[u8]) -> Result<Self, RomError> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != INES_MAGIC {
+            return Err(RomError::InvalidMagic);
+        }
+
+        let flags_6 = bytes[6];
+        let flags_7 = bytes[7];
+        let format = match flags_7 & 0b1100 == 0b1000 {
+            true => HeaderFormat::Nes2,
+            false => HeaderFormat::INes,
+        };
+
+        let (prg_rom_size, chr_rom_size) = match format {
+            HeaderFormat::Nes2 => (
+                nes2_rom_size(bytes[4], bytes[9] & 0x0F, PRG_ROM_BANK_SIZE)?,
+                nes2_rom_size(bytes[5], bytes[9] >> 4, CHR_ROM_BANK_SIZE)?,
+            ),
+            HeaderFormat::INes => (
+                bytes[4] as usize * PRG_ROM_BANK_SIZE,
+                bytes[5] as usize * CHR_ROM_BANK_SIZE,
+            ),
+        };
+
+        let mirroring = match (flags_6 & 0b1000 != 0, flags_6 & 0b0001 != 0) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+
+        let trainer_size = match flags_6 & 0b0100 != 0 {
+            true => TRAINER_SIZE,
+            false => 0,
+        };
+
+        let prg_rom_start = HEADER_SIZE + trainer_size;
+        let chr_rom_start = prg_rom_start
+            .checked_add(prg_rom_size)
+            .ok_or(RomError::SizeOverflow)?;
+        let expected = chr_rom_start
+            .checked_add(chr_rom_size)
+            .ok_or(RomError::SizeOverflow)?;
+        if bytes.len() < expected {
+            return Err(RomError::Truncated {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let battery = flags_6 & 0b0010 != 0;
+        let console_type = match flags_7 & 0b11 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::PlayChoice10,
+            // only NES 2.0 has extended console types
+            _ => match format {
+                HeaderFormat::Nes2 => ConsoleType::Extended(bytes[13] & 0x0F),
+                HeaderFormat::INes => ConsoleType::PlayChoice10,
+            },
+        };
+
+        let mut rom = Self {
+            format,
+            mapper: (flags_6 >> 4) as u16,
+            submapper: 0,
+            mirroring,
+            battery,
+            timing: Timing::Ntsc,
+            console_type,
+            trainer: (trainer_size != 0).then(|| bytes[HEADER_SIZE..prg_rom_start].to_vec()),
+            prg_rom: bytes[prg_rom_start..chr_rom_start].to_vec(),
+            chr_rom: bytes[chr_rom_start..expected].to_vec(),
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+        };
+
+        match format {
+            HeaderFormat::Nes2 => {
+                rom.mapper |= (flags_7 & 0xF0) as u16 | ((bytes[8] & 0x0F) as u16) << 8;
+                rom.submapper = bytes[8] >> 4;
+                rom.timing = match bytes[12] & 0b11 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultipleRegion,
+                    _ => Timing::Dendy,
+                };
+                rom.prg_ram_size = nes2_ram_size(bytes[10] & 0x0F);
+                rom.prg_nvram_size = nes2_ram_size(bytes[10] >> 4);
+                rom.chr_ram_size = nes2_ram_size(bytes[11] & 0x0F);
+                rom.chr_nvram_size = nes2_ram_size(bytes[11] >> 4);
+            }
+            HeaderFormat::INes => {
+                // Old dumping tools wrote their name in bytes 12-15, which garbles the upper
+                // nibble of the mapper
+                if bytes[12..16].iter().all(|&byte| byte == 0) {
+                    rom.mapper |= (flags_7 & 0xF0) as u16;
+                }
+
+                // the size is in 8KB units, where 0 means 8KB
+                let prg_ram_size = bytes[8].max(1) as usize * 0x2000;
+                match battery {
+                    true => rom.prg_nvram_size = prg_ram_size,
+                    false => rom.prg_ram_size = prg_ram_size,
+                }
+
+                if chr_rom_size == 0 {
+                    rom.chr_ram_size = CHR_ROM_BANK_SIZE;
+                }
+            }
+        }
+
+        Ok(rom)
+    }
+}
+
+/// The size of a NES 2.0 ROM area from the least and most significant parts of its size.
+/// When the most significant nibble is $F, the least significant byte is an exponent and
+/// multiplier instead.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, RomError> {
+    match msb == 0x0F {
+        true => {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = ((lsb & 0b11) as usize) * 2 + 1;
+
+            2usize
+                .checked_pow(exponent)
+                .and_then(|x| x.checked_mul(multiplier))
+                .ok_or(RomError::SizeOverflow)
+        }
+        false => Ok((((msb as usize) << 8) | lsb as usize) * unit),
+    }
+}
+
+/// The size of a NES 2.0 RAM area from its shift count, where 0 means there is none.
+fn nes2_ram_size(shift: u8) -> usize {
+    match shift {
+        0 => 0,
+        shift => 64 << shift,
+    }
+}
+
+/// The cpu side of an NROM (mapper 0) cartridge along with the 2KB of internal ram.
+///
+/// | Address       | Contents                                |
+/// |---------------|-----------------------------------------|
+/// | $0000 - $1FFF | 2KB of internal ram, mirrored           |
+/// | $6000 - $7FFF | 8KB of PRG-RAM                          |
+/// | $8000 - $FFFF | 16KB or 32KB of PRG-ROM, mirrored       |
+///
+/// Everything else reads as 0 and ignores writes. A trainer is copied into the PRG-RAM at
+/// $7000.
+#[derive(Clone, Debug)]
+pub struct Nrom {
+    pub ram: [u8; 0x800],
+    pub prg_ram: [u8; 0x2000],
+    prg_rom: Vec<u8>,
+    battery: bool,
+}
+
+impl Nrom {
+    pub fn new(rom: &Rom) -> Result<Self, RomError> {
+        if rom.mapper != 0 {
+            return Err(RomError::UnsupportedMapper(rom.mapper));
+        }
+
+        match rom.prg_rom.len() {
+            0x4000 | 0x8000 => {}
+            size => return Err(RomError::InvalidPrgRomSize(size)),
+        }
+
+        let mut prg_ram = [0; 0x2000];
+        if let Some(trainer) = &rom.trainer {
+            let len = trainer.len().min(TRAINER_SIZE);
+            prg_ram[0x1000..0x1000 + len].copy_from_slice(&trainer[..len]);
+        }
+
+        Ok(Self {
+            ram: [0; 0x800],
+            prg_ram,
+            prg_rom: rom.prg_rom.clone(),
+            battery: rom.battery,
+        })
+    }
+}
+
+impl Mapper for Nrom {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.ram[address as usize & 0x7FF],
+            0x6000..=0x7FFF => self.prg_ram[address as usize - 0x6000],
+            0x8000..=0xFFFF => self.prg_rom[(address as usize - 0x8000) % self.prg_rom.len()],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram[address as usize & 0x7FF] = byte,
+            0x6000..=0x7FFF => self.prg_ram[address as usize - 0x6000] = byte,
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.ram)?;
+        writer.write_all(&self.prg_ram)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut ram = [0; 0x800];
+        let mut prg_ram = [0; 0x2000];
+        reader.read_exact(&mut ram)?;
+        reader.read_exact(&mut prg_ram)?;
+
+        self.ram = ram;
+        self.prg_ram = prg_ram;
+        Ok(())
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0x0000, &self.ram), (0x6000, &self.prg_ram)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0x0000, &mut self.ram), (0x6000, &mut self.prg_ram)])
+    }
+}
+
+/// Only the PRG-RAM and PRG-ROM are used when NROM is the cartridge of a
+/// [`NesBus`](crate::bus::NesBus), which has its own internal ram.
+impl crate::bus::Cartridge for Nrom {
+    fn read(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0xFFFF => Some(Mapper::read(self, address)),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        Mapper::write(self, address, byte)
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&self.prg_ram[..])
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut self.prg_ram[..])
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.prg_ram)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut prg_ram = [0; 0x2000];
+        reader.read_exact(&mut prg_ram)?;
+        self.prg_ram = prg_ram;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::NoInterrupts;
+    use crate::Cpu;
+
+    fn ines(prg_banks: u8, flags_6: u8) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE + prg_banks as usize * PRG_ROM_BANK_SIZE];
+        bytes[0..4].copy_from_slice(&INES_MAGIC);
+        bytes[4] = prg_banks;
+        bytes[6] = flags_6;
+        bytes
+    }
+
+    #[test]
+    fn test_nrom() {
+        let mut bytes = ines(1, 0b0000_0001);
+        // the reset vector, at the end of the only bank
+        bytes[HEADER_SIZE + 0x3FFC] = 0x00;
+        bytes[HEADER_SIZE + 0x3FFD] = 0xC0;
+
+        let rom = Rom::from_ines(&bytes).unwrap();
+        assert_eq!(rom.mapper, 0);
+        assert_eq!(rom.mirroring, Mirroring::Vertical);
+
+        let mut nrom = Nrom::new(&rom).unwrap();
+        assert_eq!(nrom.read(0xBFFD), 0xC0);
+        assert_eq!(nrom.read(0xFFFD), 0xC0);
+
+        nrom.write(0x0801, 0x42);
+        assert_eq!(nrom.read(0x0001), 0x42);
+        assert_eq!(nrom.read(0x1801), 0x42);
+
+        nrom.write(0x8000, 0x42);
+        assert_eq!(nrom.read(0x8000), 0x00);
+
+        // a trainer is loaded at $7000
+        let mut with_trainer = rom.clone();
+        with_trainer.trainer = Some(vec![0xAA; TRAINER_SIZE]);
+        let nrom = Nrom::new(&with_trainer).unwrap();
+        assert_eq!([nrom.read(0x6FFF), nrom.read(0x7000)], [0x00, 0xAA]);
+        assert_eq!([nrom.read(0x71FF), nrom.read(0x7200)], [0xAA, 0x00]);
+
+        // boots straight into the reset vector
+        let mut cpu = Cpu::new(Nrom::new(&rom).unwrap(), NoInterrupts);
+        cpu.initialize();
+        assert_eq!(cpu.program_counter, 0xC000);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_nrom_truncated_state() {
+        use crate::bus::Cartridge;
+
+        let mut nrom = Nrom::new(&Rom::from_ines(&ines(1, 0)).unwrap()).unwrap();
+        Mapper::write(&mut nrom, 0x6000, 0x42);
+        let mut state = Vec::new();
+        Cartridge::save_state(&nrom, &mut state).unwrap();
+
+        // the PRG-RAM is kept by both the mapper and the cartridge loads
+        Mapper::write(&mut nrom, 0x6000, 0x24);
+        let truncated = &state[..state.len() - 1];
+        assert!(Cartridge::load_state(&mut nrom, &mut &truncated[..]).is_err());
+        assert!(Mapper::load_state(&mut nrom, &mut &truncated[..]).is_err());
+        assert_eq!(nrom.prg_ram[0], 0x24);
+
+        Cartridge::load_state(&mut nrom, &mut state.as_slice()).unwrap();
+        assert_eq!(nrom.prg_ram[0], 0x42);
+    }
+
+    #[test]
+    fn test_nes2() {
+        let mut bytes =
+            vec![0; HEADER_SIZE + TRAINER_SIZE + 2 * PRG_ROM_BANK_SIZE + CHR_ROM_BANK_SIZE];
+        bytes[0..4].copy_from_slice(&INES_MAGIC);
+        bytes[4] = 2;
+        bytes[5] = 1;
+        // mapper 276 with a trainer and a battery, submapper 2 on a Vs. System
+        bytes[6] = 0x46;
+        bytes[7] = 0x19;
+        bytes[8] = 0x21;
+        // 8KB of PRG-NVRAM and 8KB of CHR-RAM
+        bytes[10] = 0x70;
+        bytes[11] = 0x07;
+        bytes[12] = 0x01;
+        bytes[HEADER_SIZE] = 0xAA;
+
+        let rom = Rom::from_ines(&bytes).unwrap();
+        assert_eq!(rom.format, HeaderFormat::Nes2);
+        assert_eq!(rom.mapper, 276);
+        assert_eq!(rom.submapper, 2);
+        assert!(rom.battery);
+        assert_eq!(rom.timing, Timing::Pal);
+        assert_eq!(rom.console_type, ConsoleType::VsSystem);
+        assert_eq!(
+            rom.trainer.as_ref().map(|x| (x.len(), x[0])),
+            Some((TRAINER_SIZE, 0xAA))
+        );
+        assert_eq!(rom.prg_rom.len(), 2 * PRG_ROM_BANK_SIZE);
+        assert_eq!(rom.chr_rom.len(), CHR_ROM_BANK_SIZE);
+        assert_eq!((rom.prg_ram_size, rom.prg_nvram_size), (0, 0x2000));
+        assert_eq!((rom.chr_ram_size, rom.chr_nvram_size), (0x2000, 0));
+
+        // 2^2 * 3 bytes in exponent-multiplier form
+        assert_eq!(nes2_rom_size(0b0000_1001, 0x0F, PRG_ROM_BANK_SIZE), Ok(12));
+
+        bytes[4] = 0xFF;
+        bytes[9] = 0x0F;
+        assert_eq!(Rom::from_ines(&bytes), Err(RomError::SizeOverflow));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Rom::from_ines(b"NES"), Err(RomError::InvalidMagic));
+
+        let mut bytes = ines(2, 0);
+        bytes.truncate(HEADER_SIZE + 100);
+        assert_eq!(
+            Rom::from_ines(&bytes),
+            Err(RomError::Truncated {
+                expected: HEADER_SIZE + 0x8000,
+                actual: HEADER_SIZE + 100
+            })
+        );
+
+        let rom = Rom::from_ines(&ines(1, 0x10)).unwrap();
+        assert_eq!(Nrom::new(&rom).unwrap_err(), RomError::UnsupportedMapper(1));
+    }
+}
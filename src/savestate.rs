@@ -0,0 +1,326 @@
+use crate::processor_status::ProcessorStatus;
+use crate::{Cpu, Interrupts, Mapper, Observer, Revision, Variant};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// The bytes every savestate starts with.
+pub const SAVESTATE_MAGIC: [u8; 4] = *b"N65S";
+
+/// The version of the savestate format written by [`Cpu::save_state`]. This is bumped
+/// whenever the layout changes, and older versions are still loaded when possible.
+pub const SAVESTATE_VERSION: u16 = 2;
+
+// Bits of the flags byte
+const INITIALIZED: u8 = 0b0000_0001;
+const INTERRUPT_PENDING: u8 = 0b0000_0010;
+const NON_MASKABLE_INTERRUPT_PENDING: u8 = 0b0000_0100;
+const WAITING: u8 = 0b0000_1000;
+const STOPPED: u8 = 0b0001_0000;
+
+// The layout of version 2, all multi-byte values being little-endian:
+//
+// | Offset | Size | Field                                    |
+// |--------|------|------------------------------------------|
+// | 0      | 4    | magic                                    |
+// | 4      | 2    | version                                  |
+// | 6      | 1    | accumulator                              |
+// | 7      | 1    | x                                        |
+// | 8      | 1    | y                                        |
+// | 9      | 1    | stack pointer                            |
+// | 10     | 2    | program counter                          |
+// | 12     | 1    | processor status                         |
+// | 13     | 6    | internal registers                       |
+// | 19     | 1    | flags (initialized, interrupts, WAI/STP) |
+// | 20     | 8    | cycle count                              |
+// | 28     | 1    | variant                                  |
+// | 29     | 1    | revision                                 |
+// | 30     | ..   | mapper state, see [`Mapper::save_state`] |
+//
+// Version 1 has no variant and revision, so its mapper state starts at 28. Its flags byte
+// has no WAI/STP bits.
+const HEADER_SIZE: usize = 30;
+const VERSION_1_HEADER_SIZE: usize = 28;
+
+/// Why a savestate could not be loaded.
+#[derive(Debug)]
+pub enum SavestateError {
+    /// The data does not start with [`SAVESTATE_MAGIC`].
+    InvalidMagic,
+    /// The savestate was written by a newer version of the format.
+    UnsupportedVersion(u16),
+    /// The variant or revision in the header is not one that exists.
+    InvalidChip,
+    /// The savestate was written by a cpu emulating another chip, which is given.
+    ChipMismatch {
+        variant: Variant,
+        revision: Revision,
+    },
+    /// The savestate could not be read, or the mapper failed to load its state.
+    Io(io::Error),
+}
+
+impl fmt::Display for SavestateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SavestateError::InvalidMagic => write!(f, "not a nes6502 savestate"),
+            SavestateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported savestate version {}", version)
+            }
+            SavestateError::InvalidChip => {
+                write!(f, "savestate has an unknown variant or revision")
+            }
+            SavestateError::ChipMismatch { variant, revision } => write!(
+                f,
+                "savestate was written by a {:?} ({:?}) cpu",
+                variant, revision
+            ),
+            SavestateError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SavestateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SavestateError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SavestateError {
+    fn from(e: io::Error) -> Self {
+        SavestateError::Io(e)
+    }
+}
+
+fn variant_to_byte(variant: Variant) -> u8 {
+    match variant {
+        Variant::Ricoh2A03 => 0,
+        Variant::Nmos6502 => 1,
+        Variant::Wdc65C02 => 2,
+    }
+}
+
+fn variant_from_byte(byte: u8) -> Option<Variant> {
+    match byte {
+        0 => Some(Variant::Ricoh2A03),
+        1 => Some(Variant::Nmos6502),
+        2 => Some(Variant::Wdc65C02),
+        _ => None,
+    }
+}
+
+fn revision_to_byte(revision: Revision) -> u8 {
+    match revision {
+        Revision::Standard => 0,
+        Revision::RevA => 1,
+    }
+}
+
+fn revision_from_byte(byte: u8) -> Option<Revision> {
+    match byte {
+        0 => Some(Revision::Standard),
+        1 => Some(Revision::RevA),
+        _ => None,
+    }
+}
+
+impl<M: Mapper, I: Interrupts, O: Observer> Cpu<M, I, O> {
+    /// Writes a compact binary savestate of the cpu, including the pending interrupts
+    /// and the cycle count. Memory is written by [`Mapper::save_state`].
+    pub fn save_state(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut flags = 0;
+        if self.initialized {
+            flags |= INITIALIZED;
+        }
+        if self.interrupts.interrupt_state() {
+            flags |= INTERRUPT_PENDING;
+        }
+        if self.interrupts.non_maskable_interrupt_state() {
+            flags |= NON_MASKABLE_INTERRUPT_PENDING;
+        }
+        if self.waiting {
+            flags |= WAITING;
+        }
+        if self.stopped {
+            flags |= STOPPED;
+        }
+
+        let mut header = [0; HEADER_SIZE];
+        header[0..4].copy_from_slice(&SAVESTATE_MAGIC);
+        header[4..6].copy_from_slice(&SAVESTATE_VERSION.to_le_bytes());
+        header[6] = self.accumulator;
+        header[7] = self.x;
+        header[8] = self.y;
+        header[9] = self.stack_pointer;
+        header[10..12].copy_from_slice(&self.program_counter.to_le_bytes());
+        header[12] = self.processor_status.0;
+        header[13..19].copy_from_slice(&self.registers);
+        header[19] = flags;
+        header[20..28].copy_from_slice(&self.cycles.to_le_bytes());
+        header[28] = variant_to_byte(self.variant);
+        header[29] = revision_to_byte(self.revision);
+
+        writer.write_all(&header)?;
+        self.memory_mapper.save_state(writer)
+    }
+
+    /// Restores a savestate written by [`Self::save_state`]. Fails if the data is not a
+    /// savestate, was written by a newer version, or was written by a cpu emulating
+    /// another variant or revision, in which case nothing is changed. The shadow call stack
+    /// cannot be restored, so it is cleared.
+    pub fn load_state(&mut self, reader: &mut impl Read) -> Result<(), SavestateError> {
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header[..VERSION_1_HEADER_SIZE])?;
+
+        if header[0..4] != SAVESTATE_MAGIC {
+            return Err(SavestateError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version > SAVESTATE_VERSION {
+            return Err(SavestateError::UnsupportedVersion(version));
+        }
+
+        // version 1 did not record the chip
+        if version >= 2 {
+            reader.read_exact(&mut header[VERSION_1_HEADER_SIZE..])?;
+
+            let variant = variant_from_byte(header[28]).ok_or(SavestateError::InvalidChip)?;
+            let revision = revision_from_byte(header[29]).ok_or(SavestateError::InvalidChip)?;
+            if (variant, revision) != (self.variant, self.revision) {
+                return Err(SavestateError::ChipMismatch { variant, revision });
+            }
+        }
+
+        // the header is only committed once the mapper has loaded, so a truncated
+        // savestate leaves the cpu as it was
+        self.memory_mapper.load_state(reader)?;
+
+        let flags = header[19];
+
+        self.accumulator = header[6];
+        self.x = header[7];
+        self.y = header[8];
+        self.stack_pointer = header[9];
+        self.program_counter = u16::from_le_bytes([header[10], header[11]]);
+        self.processor_status = ProcessorStatus(header[12]);
+        self.registers.copy_from_slice(&header[13..19]);
+        self.initialized = flags & INITIALIZED != 0;
+        self.waiting = flags & WAITING != 0;
+        self.stopped = flags & STOPPED != 0;
+        self.interrupts
+            .set_interrupt_state(flags & INTERRUPT_PENDING != 0);
+        self.interrupts
+            .set_non_maskable_interrupt_state(flags & NON_MASKABLE_INTERRUPT_PENDING != 0);
+        self.cycles = u64::from_le_bytes(header[20..28].try_into().unwrap());
+
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.clear();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{InterruptsContainer, Memory};
+
+    fn new_cpu() -> Cpu<Memory, InterruptsContainer> {
+        // LDX #$05; loop: DEX; STX $10; BNE loop
+        let memory = Memory::with_program(0x8000, &[0xA2, 0x05, 0xCA, 0x86, 0x10, 0xD0, 0xFB]);
+
+        let mut cpu = Cpu::new(memory, InterruptsContainer::default());
+        cpu.initialize();
+        cpu
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut cpu = new_cpu();
+        for _ in 0..4 {
+            cpu.cycle();
+        }
+        cpu.interrupts.non_maskable_interrupt = true;
+
+        let mut savestate = Vec::new();
+        cpu.save_state(&mut savestate).unwrap();
+        assert_eq!(savestate.len(), HEADER_SIZE + 0x10000);
+
+        let mut loaded = Cpu::new(Memory([0; 0x10000]), InterruptsContainer::default());
+        loaded.load_state(&mut savestate.as_slice()).unwrap();
+
+        assert_eq!(loaded.state(), cpu.state());
+        assert_eq!(loaded.cycles, cpu.cycles);
+        assert!(loaded.initialized);
+        assert!(loaded.interrupts.non_maskable_interrupt);
+        assert!(!loaded.interrupts.interrupt);
+
+        // both should keep running identically
+        for _ in 0..8 {
+            assert_eq!(loaded.cycle(), cpu.cycle());
+        }
+        assert_eq!(loaded.state(), cpu.state());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut cpu = new_cpu();
+        for _ in 0..4 {
+            cpu.cycle();
+        }
+        let mut savestate = Vec::new();
+        cpu.save_state(&mut savestate).unwrap();
+        savestate.truncate(HEADER_SIZE + 0x100);
+
+        let mut loaded = new_cpu();
+        let (state, cycles) = (loaded.state(), loaded.cycles);
+        let error = loaded.load_state(&mut savestate.as_slice()).unwrap_err();
+        assert!(matches!(error, SavestateError::Io(_)));
+        assert_eq!(loaded.state(), state);
+        assert_eq!(loaded.cycles, cycles);
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut cpu = new_cpu();
+        let error = cpu.load_state(&mut [0u8; 64].as_slice()).unwrap_err();
+        assert!(matches!(error, SavestateError::InvalidMagic));
+    }
+
+    #[test]
+    fn test_chip_mismatch() {
+        let cpu = new_cpu().with_revision(Revision::RevA);
+        let mut savestate = Vec::new();
+        cpu.save_state(&mut savestate).unwrap();
+
+        let mut loaded = new_cpu().with_variant(Variant::Nmos6502);
+        let error = loaded.load_state(&mut savestate.as_slice()).unwrap_err();
+        assert!(matches!(
+            error,
+            SavestateError::ChipMismatch {
+                variant: Variant::Ricoh2A03,
+                revision: Revision::RevA
+            }
+        ));
+    }
+
+    #[test]
+    fn test_version_1() {
+        let cpu = new_cpu();
+        let mut savestate = Vec::new();
+        cpu.save_state(&mut savestate).unwrap();
+
+        // drop the variant and revision
+        savestate[4..6].copy_from_slice(&1u16.to_le_bytes());
+        savestate.drain(VERSION_1_HEADER_SIZE..HEADER_SIZE);
+
+        let mut loaded = new_cpu().with_variant(Variant::Wdc65C02);
+        loaded.load_state(&mut savestate.as_slice()).unwrap();
+        assert_eq!(loaded.state(), cpu.state());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::CpuState;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+/// A register of [`CpuState`].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Register {
+    Pc,
+    S,
+    A,
+    X,
+    Y,
+    P,
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Register::Pc => "pc",
+            Register::S => "s",
+            Register::A => "a",
+            Register::X => "x",
+            Register::Y => "y",
+            Register::P => "p",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// A register that differs between two states. The program counter is the only register
+/// that needs the full `u16`.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct RegisterDifference {
+    pub register: Register,
+    pub expected: u16,
+    pub actual: u16,
+}
+
+/// A memory location that differs between two states. Locations missing from a state
+/// are zero.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct MemoryDifference {
+    pub address: u16,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Every difference between two [`CpuState`]s, as returned by [`CpuState::diff`]. Its
+/// [`fmt::Display`] implementation prints one difference per line.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct StateDiff {
+    pub registers: Vec<RegisterDifference>,
+    /// Sorted by address.
+    pub memory: Vec<MemoryDifference>,
+}
+
+impl StateDiff {
+    /// Returns true if the states were equal.
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for difference in &self.registers {
+            let width = match difference.register {
+                Register::Pc => 4,
+                _ => 2,
+            };
+
+            writeln!(
+                f,
+                "{:>5}: expected ${:0width$X}, got ${:0width$X}",
+                difference.register.to_string(),
+                difference.expected,
+                difference.actual,
+            )?;
+        }
+
+        for difference in &self.memory {
+            writeln!(
+                f,
+                "${:04X}: expected ${:02X}, got ${:02X}",
+                difference.address, difference.expected, difference.actual
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CpuState {
+    /// Compares this state against the `expected` one, listing every register and memory
+    /// location that differs.
+    pub fn diff(&self, expected: &CpuState) -> StateDiff {
+        let registers = [
+            (Register::Pc, expected.pc, self.pc),
+            (Register::S, expected.s as u16, self.s as u16),
+            (Register::A, expected.a as u16, self.a as u16),
+            (Register::X, expected.x as u16, self.x as u16),
+            (Register::Y, expected.y as u16, self.y as u16),
+            (Register::P, expected.p as u16, self.p as u16),
+        ]
+        .into_iter()
+        .filter(|(_, expected, actual)| expected != actual)
+        .map(|(register, expected, actual)| RegisterDifference {
+            register,
+            expected,
+            actual,
+        })
+        .collect();
+
+        // both are sorted, so they can be merged
+        let expected_ram = expected.normalized_ram();
+        let actual_ram = self.normalized_ram();
+        let mut expected_ram = expected_ram.iter().peekable();
+        let mut actual_ram = actual_ram.iter().peekable();
+        let mut memory = Vec::new();
+
+        loop {
+            let difference = match (expected_ram.peek(), actual_ram.peek()) {
+                (None, None) => break,
+                (Some(&&(address, expected)), Some(&&(actual_address, actual)))
+                    if address == actual_address =>
+                {
+                    expected_ram.next();
+                    actual_ram.next();
+                    MemoryDifference {
+                        address,
+                        expected,
+                        actual,
+                    }
+                }
+                (Some(&&(address, expected)), Some(&&(actual_address, _)))
+                    if address < actual_address =>
+                {
+                    expected_ram.next();
+                    MemoryDifference {
+                        address,
+                        expected,
+                        actual: 0,
+                    }
+                }
+                (Some(&&(address, expected)), None) => {
+                    expected_ram.next();
+                    MemoryDifference {
+                        address,
+                        expected,
+                        actual: 0,
+                    }
+                }
+                (_, Some(&&(address, actual))) => {
+                    actual_ram.next();
+                    MemoryDifference {
+                        address,
+                        expected: 0,
+                        actual,
+                    }
+                }
+            };
+
+            if difference.expected != difference.actual {
+                memory.push(difference);
+            }
+        }
+
+        StateDiff { registers, memory }
+    }
+}
+
+/// Returned by [`crate::Cpu::from_state`] when the state read back from the cpu does not
+/// match the one it was created from, which happens when the mapper has read-only,
+/// mirrored or unmapped addresses.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct StateMismatch {
+    pub diff: StateDiff,
+}
+
+impl fmt::Display for StateMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "cpu state does not match the state it was created from:")?;
+        write!(f, "{}", self.diff)
+    }
+}
+
+impl Error for StateMismatch {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let expected = CpuState {
+            pc: 0x8000,
+            a: 0x05,
+            ram: vec![(0x0010, 0x01), (0x0200, 0x02), (0x0300, 0x03)],
+            ..Default::default()
+        };
+        let actual = CpuState {
+            pc: 0x8000,
+            a: 0x06,
+            ram: vec![(0x0300, 0x04), (0x0100, 0x05), (0x0010, 0x01)],
+            ..Default::default()
+        };
+
+        let diff = actual.diff(&expected);
+        assert_eq!(
+            diff.registers,
+            [RegisterDifference {
+                register: Register::A,
+                expected: 0x05,
+                actual: 0x06
+            }]
+        );
+        assert_eq!(
+            diff.memory,
+            [
+                MemoryDifference {
+                    address: 0x0100,
+                    expected: 0x00,
+                    actual: 0x05
+                },
+                MemoryDifference {
+                    address: 0x0200,
+                    expected: 0x02,
+                    actual: 0x00
+                },
+                MemoryDifference {
+                    address: 0x0300,
+                    expected: 0x03,
+                    actual: 0x04
+                },
+            ]
+        );
+        assert!(expected.diff(&expected).is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{Interrupts, Mapper};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// 64KB of flat RAM, which is saved whole and snapshotted as a single region.
+pub(crate) struct Memory(pub [u8; 0x10000]);
+
+impl Memory {
+    /// Memory holding `program` at `address`, with the reset vector pointing to it.
+    pub fn with_program(address: u16, program: &[u8]) -> Self {
+        let mut memory = Memory([0; 0x10000]);
+        let start = address as usize;
+        memory.0[start..start + program.len()].copy_from_slice(program);
+        memory.0[0xFFFC..0xFFFE].copy_from_slice(&address.to_le_bytes());
+        memory
+    }
+}
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0[address as usize] = byte
+    }
+
+    #[cfg(feature = "std")]
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut bytes = [0; 0x10000];
+        reader.read_exact(&mut bytes)?;
+        self.0 = bytes;
+        Ok(())
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0, &self.0)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0, &mut self.0)])
+    }
+}
+
+pub(crate) struct NoInterrupts;
+
+impl Interrupts for NoInterrupts {
+    fn interrupt_state(&self) -> bool {
+        false
+    }
+
+    fn set_interrupt_state(&mut self, _new_state: bool) {}
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        false
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, _new_state: bool) {}
+}
+
+/// An IRQ and an NMI line that tests can raise, and that the cpu clears when it services
+/// them.
+#[derive(Default)]
+pub(crate) struct InterruptsContainer {
+    pub interrupt: bool,
+    pub non_maskable_interrupt: bool,
+}
+
+impl Interrupts for InterruptsContainer {
+    fn interrupt_state(&self) -> bool {
+        self.interrupt
+    }
+
+    fn set_interrupt_state(&mut self, new_state: bool) {
+        self.interrupt = new_state;
+    }
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        self.non_maskable_interrupt
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, new_state: bool) {
+        self.non_maskable_interrupt = new_state;
+    }
+}
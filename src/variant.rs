@@ -0,0 +1,239 @@
+use crate::instruction::{OpcodeInfo, OPCODE_TABLE, REV_A_OPCODE_TABLE, WDC65C02_OPCODE_TABLE};
+
+/// The chip being emulated. This decides what the decimal flag does.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// The Ricoh 2A03 of the NES, which has the decimal flag but no decimal mode.
+    #[default]
+    Ricoh2A03,
+    /// The original NMOS 6502, as used in the Apple II and (as the 6510) the C64. ADC and
+    /// SBC work in BCD when the decimal flag is set.
+    Nmos6502,
+    /// The CMOS WDC 65C02, which adds instructions, fixes the `JMP ($xxFF)` bug and sets the
+    /// N and Z flags properly in decimal mode.
+    Wdc65C02,
+}
+
+/// The revision of an NMOS chip, for the bugs that were fixed in later revisions. It
+/// changes how opcodes decode, see [`Variant::opcode_table`].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Revision {
+    /// Every revision since mid-1976, which is what the 2A03 is based on.
+    #[default]
+    Standard,
+    /// The first 6502s, which lacked the rotate logic. The ROR opcodes decode to
+    /// [`Opcode::RORRevA`](crate::Opcode::RORRevA), which shifts left like ASL, shifting in
+    /// a 0, but does not change the carry flag. It takes as many cycles as ROR.
+    RevA,
+}
+
+impl Variant {
+    /// Returns true if ADC and SBC use BCD when the decimal flag is set.
+    pub fn has_decimal_mode(self) -> bool {
+        match self {
+            Variant::Ricoh2A03 => false,
+            Variant::Nmos6502 | Variant::Wdc65C02 => true,
+        }
+    }
+
+    /// Returns true for the 65C02, which behaves differently from the NMOS chips in a few
+    /// places besides its additional instructions.
+    pub fn is_cmos(self) -> bool {
+        self == Variant::Wdc65C02
+    }
+
+    /// Returns the table that opcode bytes are decoded with by a chip of this variant at
+    /// `revision`. The 65C02 ignores the revision.
+    pub fn opcode_table(self, revision: Revision) -> &'static [Option<OpcodeInfo>; 256] {
+        match (self, revision) {
+            (Variant::Ricoh2A03 | Variant::Nmos6502, Revision::Standard) => &OPCODE_TABLE,
+            (Variant::Ricoh2A03 | Variant::Nmos6502, Revision::RevA) => &REV_A_OPCODE_TABLE,
+            (Variant::Wdc65C02, _) => &WDC65C02_OPCODE_TABLE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{Memory, NoInterrupts};
+    use crate::Cpu;
+
+    /// Runs `opcode #operand` with the decimal flag set, returning the accumulator and
+    /// processor status.
+    fn run(variant: Variant, opcode: u8, accumulator: u8, operand: u8, carry: bool) -> (u8, u8) {
+        let mut memory = Memory([0; 0x10000]);
+        memory.0[0x0200] = opcode;
+        memory.0[0x0201] = operand;
+
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_variant(variant);
+        cpu.initialized = true;
+        cpu.program_counter = 0x0200;
+        cpu.accumulator = accumulator;
+        cpu.processor_status.0 = 0b0010_1000 | carry as u8;
+        cpu.cycle();
+
+        (cpu.accumulator, cpu.processor_status.0)
+    }
+
+    #[test]
+    fn test_decimal_mode() {
+        // 99 + 1 carries out to 00, with N set and Z clear as the binary sum is $9A
+        assert_eq!(run(Variant::Nmos6502, 0x69, 0x99, 0x01, false), (0x00, 0b1010_1001));
+        // 79 + 0 + 1 sets V
+        assert_eq!(run(Variant::Nmos6502, 0x69, 0x79, 0x00, true), (0x80, 0b1110_1000));
+        // 0 - 1 borrows, wrapping to 99
+        assert_eq!(run(Variant::Nmos6502, 0xE9, 0x00, 0x01, true), (0x99, 0b1010_1000));
+        // 46 - 12
+        assert_eq!(run(Variant::Nmos6502, 0xE9, 0x46, 0x12, true), (0x34, 0b0010_1001));
+
+        // the decimal flag is ignored by the 2A03
+        assert_eq!(run(Variant::Ricoh2A03, 0x69, 0x99, 0x01, false), (0x9A, 0b1010_1000));
+
+        // the 65C02 sets N and Z from the result
+        assert_eq!(run(Variant::Wdc65C02, 0x69, 0x99, 0x01, false), (0x00, 0b0010_1011));
+        assert_eq!(run(Variant::Wdc65C02, 0xE9, 0x00, 0x01, true), (0x99, 0b1010_1000));
+    }
+
+    #[test]
+    fn test_65c02_instructions() {
+        #[rustfmt::skip]
+        let program = [
+            0xA2, 0x05,       // LDX #$05
+            0xDA,             // PHX
+            0x7A,             // PLY
+            0x64, 0x20,       // STZ $20
+            0xA9, 0x81,       // LDA #$81
+            0x04, 0x21,       // TSB $21
+            0x87, 0x22,       // SMB0 $22
+            0x1A,             // INC A
+            0x92, 0x30,       // STA ($30)
+            0x0F, 0x22, 0x01, // BBR0 $22,+1
+            0x80, 0x01,       // BRA +1
+            0xDB,             // STP
+            0x6C, 0xFF, 0x02, // JMP ($02FF)
+        ];
+
+        let mut memory = Memory([0; 0x10000]);
+        memory.0[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+        memory.0[0x20] = 0xFF;
+        memory.0[0x21] = 0x01;
+        memory.0[0x30..0x32].copy_from_slice(&[0x00, 0x04]);
+        // the NMOS 6502 would read the high byte of the target from $0200
+        memory.0[0x02FF] = 0x00;
+        memory.0[0x0300] = 0x05;
+        memory.0[0x0500] = 0xCB; // WAI
+
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_variant(Variant::Wdc65C02);
+        cpu.initialized = true;
+        cpu.program_counter = 0x0200;
+        for _ in 0..13 {
+            cpu.cycle();
+        }
+
+        assert_eq!(cpu.y, 0x05);
+        assert_eq!(cpu.memory_mapper.0[0x20], 0x00);
+        assert_eq!(cpu.memory_mapper.0[0x21], 0x81);
+        assert_eq!(cpu.memory_mapper.0[0x22], 0x01);
+        assert_eq!(cpu.memory_mapper.0[0x0400], 0x82);
+        assert_eq!(cpu.program_counter, 0x0501);
+        assert!(cpu.waiting);
+        assert_eq!(cpu.cycle(), 1);
+        assert_eq!(cpu.program_counter, 0x0501);
+    }
+
+    #[test]
+    fn test_rev_a_decode() {
+        use crate::{FullOpcode, Instruction, Opcode};
+
+        // every ROR opcode, and nothing else, decodes differently
+        for byte in 0..=255 {
+            let standard = FullOpcode::try_new_for(byte, Variant::Nmos6502, Revision::Standard);
+            let rev_a = FullOpcode::try_new_for(byte, Variant::Nmos6502, Revision::RevA);
+            match standard.as_ref().map(|x| x.opcode) {
+                Some(Opcode::ROR) => assert_eq!(rev_a.unwrap().opcode, Opcode::RORRevA),
+                _ => assert_eq!(rev_a, standard),
+            }
+        }
+
+        let opcode = |variant| FullOpcode::try_new_for(0x6A, variant, Revision::RevA).unwrap();
+        assert_eq!(opcode(Variant::Ricoh2A03).opcode, Opcode::RORRevA);
+        assert_eq!(opcode(Variant::Wdc65C02).opcode, Opcode::ROR);
+
+        let instruction =
+            Instruction::decode_for(0, Variant::Nmos6502, Revision::RevA, |_| 0x6A).unwrap();
+        assert_eq!(instruction.to_string(), "ROR A");
+    }
+
+    #[test]
+    fn test_65c02_cycles() {
+        // from the WDC datasheet, without page crossings and taken branches
+        let expected = [
+            (0x80, 3), // BRA, which is always taken
+            (0xDA, 3), // PHX
+            (0x5A, 3), // PHY
+            (0xFA, 4), // PLX
+            (0x7A, 4), // PLY
+            (0x64, 3), // STZ zp
+            (0x74, 4), // STZ zp,X
+            (0x9C, 4), // STZ abs
+            (0x9E, 5), // STZ abs,X
+            (0x04, 5), // TSB zp
+            (0x0C, 6), // TSB abs
+            (0x14, 5), // TRB zp
+            (0x1C, 6), // TRB abs
+            (0x12, 5), // ORA (zp)
+            (0x32, 5), // AND (zp)
+            (0x52, 5), // EOR (zp)
+            (0x72, 5), // ADC (zp)
+            (0x92, 5), // STA (zp)
+            (0xB2, 5), // LDA (zp)
+            (0xD2, 5), // CMP (zp)
+            (0xF2, 5), // SBC (zp)
+            (0x1A, 2), // INC A
+            (0x3A, 2), // DEC A
+            (0x89, 2), // BIT #
+            (0x34, 4), // BIT zp,X
+            (0x3C, 4), // BIT abs,X
+            (0x6C, 6), // JMP (abs)
+            (0x7C, 6), // JMP (abs,X)
+            (0x0F, 5), // BBR0
+            (0xFF, 5), // BBS7
+            (0x07, 5), // RMB0
+            (0xF7, 5), // SMB7
+            (0xCB, 3), // WAI
+            (0xDB, 3), // STP
+            (0x1E, 6), // ASL abs,X
+            (0x3E, 6), // ROL abs,X
+            (0x5E, 6), // LSR abs,X
+            (0x7E, 6), // ROR abs,X
+            (0xDE, 7), // DEC abs,X
+            (0xFE, 7), // INC abs,X
+        ];
+
+        let table = Variant::Wdc65C02.opcode_table(Revision::Standard);
+        for (byte, cycles) in expected {
+            assert_eq!(
+                table[byte].unwrap().base_cycles,
+                cycles,
+                "opcode ${:02X}",
+                byte
+            );
+        }
+
+        // the NMOS chips take a cycle longer for shifts with abs,X
+        assert_eq!(OPCODE_TABLE[0x1E].unwrap().base_cycles, 7);
+
+        // while the 65C02 takes one more only when the page changes
+        let mut memory = Memory([0; 0x10000]);
+        // ASL $1000,X; ASL $10F0,X
+        memory.0[0x0200..0x0206].copy_from_slice(&[0x1E, 0x00, 0x10, 0x1E, 0xF0, 0x10]);
+        let mut cpu = Cpu::new(memory, NoInterrupts).with_variant(Variant::Wdc65C02);
+        cpu.initialized = true;
+        cpu.program_counter = 0x0200;
+        cpu.x = 0x20;
+        assert_eq!([cpu.cycle(), cpu.cycle()], [6, 7]);
+    }
+}
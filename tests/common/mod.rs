@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests and the benchmarks.
+
+#![allow(dead_code)]
+
+use nes6502::{Interrupts, Mapper};
+
+/// 64KB of flat RAM, which is snapshotted as a single region.
+pub struct Memory(pub [u8; 0x10000]);
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.0[address as usize] = byte
+    }
+
+    fn snapshot_regions(&self) -> Option<Vec<(u16, &[u8])>> {
+        Some(vec![(0, &self.0)])
+    }
+
+    fn restore_regions(&mut self) -> Option<Vec<(u16, &mut [u8])>> {
+        Some(vec![(0, &mut self.0)])
+    }
+}
+
+pub struct NoInterrupts;
+
+impl Interrupts for NoInterrupts {
+    fn interrupt_state(&self) -> bool {
+        false
+    }
+
+    fn set_interrupt_state(&mut self, _new_state: bool) {}
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        false
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, _new_state: bool) {}
+}
+
+/// An IRQ and an NMI line that can be raised, and that the cpu clears when it services them.
+#[derive(Default)]
+pub struct InterruptsContainer {
+    pub interrupt: bool,
+    pub non_maskable_interrupt: bool,
+}
+
+impl Interrupts for InterruptsContainer {
+    fn interrupt_state(&self) -> bool {
+        self.interrupt
+    }
+
+    fn set_interrupt_state(&mut self, new_state: bool) {
+        self.interrupt = new_state;
+    }
+
+    fn non_maskable_interrupt_state(&self) -> bool {
+        self.non_maskable_interrupt
+    }
+
+    fn set_non_maskable_interrupt_state(&mut self, new_state: bool) {
+        self.non_maskable_interrupt = new_state;
+    }
+}
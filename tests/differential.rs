@@ -0,0 +1,123 @@
+//! Runs random official instructions from random states through both the cpu and the
+//! reference interpreter in `reference/`, comparing the resulting state, cycles and bus
+//! accesses.
+
+mod common;
+mod reference;
+
+use common::NoInterrupts;
+use nes6502::{Cpu, CpuState, Instruction, Mapper, Observer};
+use proptest::prelude::*;
+use reference::{Access, Memory, Reference, OPCODES};
+
+impl Mapper for Memory {
+    fn read(&self, address: u16) -> u8 {
+        self.get(address)
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.set(address, byte)
+    }
+}
+
+#[derive(Default)]
+struct Trace(Vec<Access>);
+
+impl Observer for Trace {
+    fn on_instruction(&mut self, _address: u16, _instruction: &Instruction) {}
+
+    fn on_read(&mut self, address: u16, value: u8) {
+        self.0.push(Access::Read(address, value))
+    }
+
+    fn on_write(&mut self, address: u16, value: u8) {
+        self.0.push(Access::Write(address, value))
+    }
+}
+
+/// The registers along with every byte that was written.
+fn state(pc: u16, s: u8, a: u8, x: u8, y: u8, p: u8, memory: &Memory) -> CpuState {
+    CpuState {
+        pc,
+        s,
+        a,
+        x,
+        y,
+        p,
+        ram: memory.written.iter().map(|(&k, &v)| (k, v)).collect(),
+    }
+}
+
+prop_compose! {
+    fn arbitrary_reference()(
+        pc in any::<u16>(),
+        s in any::<u8>(),
+        a in any::<u8>(),
+        x in any::<u8>(),
+        y in any::<u8>(),
+        // bit 5 always reads as set and the break flag only exists when pushed
+        p in any::<u8>().prop_map(|p| (p | 0x20) & !0x10),
+        opcode in proptest::sample::select(OPCODES.map(|(opcode, ..)| opcode).to_vec()),
+        operands in any::<[u8; 2]>(),
+        seed in any::<u32>(),
+    ) -> Reference {
+        let mut memory = Memory::new(seed);
+        memory.fixed.insert(pc, opcode);
+        memory.fixed.insert(pc.wrapping_add(1), operands[0]);
+        memory.fixed.insert(pc.wrapping_add(2), operands[1]);
+
+        Reference { pc, s, a, x, y, p, memory, trace: Vec::new() }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(4096))]
+
+    #[test]
+    fn matches_reference(mut reference in arbitrary_reference()) {
+        let mut trace = Trace::default();
+        let mut cpu = Cpu::new(reference.memory.clone(), NoInterrupts).with_observer(&mut trace);
+        cpu.program_counter = reference.pc;
+        cpu.stack_pointer = reference.s;
+        cpu.accumulator = reference.a;
+        cpu.x = reference.x;
+        cpu.y = reference.y;
+        cpu.processor_status.0 = reference.p;
+        cpu.initialized = true;
+
+        let expected_cycles = reference.step().unwrap();
+        let (cycles, success, instruction) = cpu.cycle_debug();
+        prop_assert!(success);
+
+        let expected = state(reference.pc, reference.s, reference.a, reference.x, reference.y, reference.p, &reference.memory);
+        let actual = state(
+            cpu.program_counter,
+            cpu.stack_pointer,
+            cpu.accumulator,
+            cpu.x,
+            cpu.y,
+            cpu.processor_status.0,
+            &cpu.memory_mapper,
+        );
+        drop(cpu);
+
+        let diff = actual.diff(&expected);
+        prop_assert!(diff.is_empty(), "{}\n{}", instruction.unwrap(), diff);
+        prop_assert_eq!(cycles, expected_cycles, "{}", instruction.unwrap());
+        prop_assert_eq!(&trace.0, &reference.trace, "{}", instruction.unwrap());
+    }
+}
+
+#[test]
+fn opcode_table_matches_reference() {
+    for (byte, info) in nes6502::OPCODE_TABLE.iter().enumerate() {
+        let reference = OPCODES.iter().find(|(opcode, ..)| *opcode as usize == byte);
+        match (info, reference) {
+            (Some(info), Some(&(_, _, _, cycles))) => {
+                assert_eq!(info.base_cycles, cycles, "opcode ${:02X}", byte)
+            }
+            (None, None) => {}
+            _ => panic!("opcode ${:02X} is legal in only one of the tables", byte),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Runs nestest (https://www.qmtpro.com/~nes/misc/nestest.txt) from $C000, which is its
+//! automation mode, and compares every instruction against `nestest.log`.
+//!
+//! Set `NESTEST_ROM` to the path of `nestest.nes`. The log is read from `nestest.log` next
+//! to it, or from `NESTEST_LOG`. The test is skipped when the ROM is not set.
+
+mod common;
+
+use common::NoInterrupts;
+use nes6502::rom::{Nrom, Rom};
+use nes6502::Cpu;
+use std::path::PathBuf;
+
+const ROM_ENVIRONMENT_VARIABLE: &str = "NESTEST_ROM";
+const LOG_ENVIRONMENT_VARIABLE: &str = "NESTEST_LOG";
+
+/// The parts of a log line that are compared.
+#[derive(PartialEq, Debug)]
+struct TraceLine {
+    pc: u16,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    sp: u8,
+    cycles: u64,
+}
+
+impl TraceLine {
+    fn parse(line: &str) -> Option<Self> {
+        let field = |name: &str| {
+            line.split_whitespace()
+                .find_map(|token| token.strip_prefix(name))
+        };
+        let register = |name: &str| u8::from_str_radix(field(name)?, 16).ok();
+
+        Some(Self {
+            pc: u16::from_str_radix(line.get(0..4)?, 16).ok()?,
+            a: register("A:")?,
+            x: register("X:")?,
+            y: register("Y:")?,
+            p: register("P:")?,
+            sp: register("SP:")?,
+            cycles: field("CYC:")?.parse().ok()?,
+        })
+    }
+}
+
+/// Unofficial opcodes are marked with a `*` before their mnemonic.
+fn is_unofficial(line: &str) -> bool {
+    line.get(15..16) == Some("*")
+}
+
+#[test]
+fn nestest() {
+    let rom_path = match std::env::var_os(ROM_ENVIRONMENT_VARIABLE) {
+        Some(x) => PathBuf::from(x),
+        None => {
+            eprintln!(
+                "skipping nestest: set {} to the path of nestest.nes",
+                ROM_ENVIRONMENT_VARIABLE
+            );
+            return;
+        }
+    };
+    let log_path = match std::env::var_os(LOG_ENVIRONMENT_VARIABLE) {
+        Some(x) => PathBuf::from(x),
+        None => rom_path.with_extension("log"),
+    };
+
+    let rom = Rom::from_ines(&std::fs::read(&rom_path).unwrap()).unwrap();
+    let log = std::fs::read_to_string(&log_path).unwrap();
+
+    let mut cpu = Cpu::new(Nrom::new(&rom).unwrap(), NoInterrupts);
+    cpu.initialize();
+    cpu.program_counter = 0xC000;
+    cpu.processor_status.0 = 0x24;
+
+    let mut compared = 0;
+    for (index, line) in log.lines().enumerate() {
+        // the cpu does not implement unofficial opcodes, so the official tests are done
+        if is_unofficial(line) {
+            break;
+        }
+
+        let expected = TraceLine::parse(line)
+            .unwrap_or_else(|| panic!("could not parse line {}: {}", index + 1, line));
+        let actual = TraceLine {
+            pc: cpu.program_counter,
+            a: cpu.accumulator,
+            x: cpu.x,
+            y: cpu.y,
+            p: cpu.processor_status.0,
+            sp: cpu.stack_pointer,
+            cycles: cpu.cycles,
+        };
+
+        assert_eq!(
+            actual,
+            expected,
+            "mismatch at line {}: {}",
+            index + 1,
+            line
+        );
+        // the result of the unofficial tests is only written once they run
+        assert_eq!(
+            cpu.peek(0x0003),
+            0x00,
+            "unofficial opcode result set at line {}: {}",
+            index + 1,
+            line
+        );
+
+        let (_, success, _) = cpu.cycle_debug();
+        assert!(success, "illegal opcode at line {}: {}", index + 1, line);
+        compared += 1;
+    }
+
+    assert!(compared > 0, "{} is empty", log_path.display());
+
+    // nestest stores the number of the first failed official test at $02 and unofficial
+    // test at $03. The unofficial tests are never reached, so $03 is still 0.
+    assert_eq!(cpu.peek(0x0002), 0x00, "official opcode tests failed");
+    assert_eq!(cpu.peek(0x0003), 0x00, "unofficial opcode tests failed");
+}
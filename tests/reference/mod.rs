@@ -0,0 +1,482 @@
+//! A small reference interpreter of the official NES 6502 instructions, written
+//! independently of the crate so the two can be compared. It favors being obviously
+//! correct over being fast: every instruction is decoded from a single table and
+//! executed by one match on its mnemonic.
+
+use std::collections::BTreeMap;
+
+const CARRY: u8 = 0x01;
+const ZERO: u8 = 0x02;
+const INTERRUPT_DISABLE: u8 = 0x04;
+const BREAK: u8 = 0x10;
+const OVERFLOW: u8 = 0x40;
+const NEGATIVE: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    Zeropage,
+    ZeropageX,
+    ZeropageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+#[rustfmt::skip]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mnemonic {
+    Adc, And, Asl, Bcc, Bcs, Beq, Bit, Bmi, Bne, Bpl, Brk, Bvc, Bvs, Clc, Cld, Cli, Clv,
+    Cmp, Cpx, Cpy, Dec, Dex, Dey, Eor, Inc, Inx, Iny, Jmp, Jsr, Lda, Ldx, Ldy, Lsr, Nop,
+    Ora, Pha, Php, Pla, Plp, Rol, Ror, Rti, Rts, Sbc, Sec, Sed, Sei, Sta, Stx, Sty, Tax,
+    Tay, Tsx, Txa, Txs, Tya,
+}
+
+use Mnemonic::*;
+use Mode::*;
+
+/// (opcode, mnemonic, mode, base cycles) of every official opcode.
+#[rustfmt::skip]
+pub const OPCODES: [(u8, Mnemonic, Mode, u8); 151] = [
+    (0x69, Adc, Immediate, 2), (0x65, Adc, Zeropage, 3), (0x75, Adc, ZeropageX, 4),
+    (0x6D, Adc, Absolute, 4), (0x7D, Adc, AbsoluteX, 4), (0x79, Adc, AbsoluteY, 4),
+    (0x61, Adc, IndirectX, 6), (0x71, Adc, IndirectY, 5),
+    (0x29, And, Immediate, 2), (0x25, And, Zeropage, 3), (0x35, And, ZeropageX, 4),
+    (0x2D, And, Absolute, 4), (0x3D, And, AbsoluteX, 4), (0x39, And, AbsoluteY, 4),
+    (0x21, And, IndirectX, 6), (0x31, And, IndirectY, 5),
+    (0x0A, Asl, Accumulator, 2), (0x06, Asl, Zeropage, 5), (0x16, Asl, ZeropageX, 6),
+    (0x0E, Asl, Absolute, 6), (0x1E, Asl, AbsoluteX, 7),
+    (0x90, Bcc, Relative, 2), (0xB0, Bcs, Relative, 2), (0xF0, Beq, Relative, 2),
+    (0x30, Bmi, Relative, 2), (0xD0, Bne, Relative, 2), (0x10, Bpl, Relative, 2),
+    (0x50, Bvc, Relative, 2), (0x70, Bvs, Relative, 2),
+    (0x24, Bit, Zeropage, 3), (0x2C, Bit, Absolute, 4),
+    (0x00, Brk, Implied, 7),
+    (0x18, Clc, Implied, 2), (0xD8, Cld, Implied, 2), (0x58, Cli, Implied, 2),
+    (0xB8, Clv, Implied, 2),
+    (0xC9, Cmp, Immediate, 2), (0xC5, Cmp, Zeropage, 3), (0xD5, Cmp, ZeropageX, 4),
+    (0xCD, Cmp, Absolute, 4), (0xDD, Cmp, AbsoluteX, 4), (0xD9, Cmp, AbsoluteY, 4),
+    (0xC1, Cmp, IndirectX, 6), (0xD1, Cmp, IndirectY, 5),
+    (0xE0, Cpx, Immediate, 2), (0xE4, Cpx, Zeropage, 3), (0xEC, Cpx, Absolute, 4),
+    (0xC0, Cpy, Immediate, 2), (0xC4, Cpy, Zeropage, 3), (0xCC, Cpy, Absolute, 4),
+    (0xC6, Dec, Zeropage, 5), (0xD6, Dec, ZeropageX, 6), (0xCE, Dec, Absolute, 6),
+    (0xDE, Dec, AbsoluteX, 7),
+    (0xCA, Dex, Implied, 2), (0x88, Dey, Implied, 2),
+    (0x49, Eor, Immediate, 2), (0x45, Eor, Zeropage, 3), (0x55, Eor, ZeropageX, 4),
+    (0x4D, Eor, Absolute, 4), (0x5D, Eor, AbsoluteX, 4), (0x59, Eor, AbsoluteY, 4),
+    (0x41, Eor, IndirectX, 6), (0x51, Eor, IndirectY, 5),
+    (0xE6, Inc, Zeropage, 5), (0xF6, Inc, ZeropageX, 6), (0xEE, Inc, Absolute, 6),
+    (0xFE, Inc, AbsoluteX, 7),
+    (0xE8, Inx, Implied, 2), (0xC8, Iny, Implied, 2),
+    (0x4C, Jmp, Absolute, 3), (0x6C, Jmp, Indirect, 5), (0x20, Jsr, Absolute, 6),
+    (0xA9, Lda, Immediate, 2), (0xA5, Lda, Zeropage, 3), (0xB5, Lda, ZeropageX, 4),
+    (0xAD, Lda, Absolute, 4), (0xBD, Lda, AbsoluteX, 4), (0xB9, Lda, AbsoluteY, 4),
+    (0xA1, Lda, IndirectX, 6), (0xB1, Lda, IndirectY, 5),
+    (0xA2, Ldx, Immediate, 2), (0xA6, Ldx, Zeropage, 3), (0xB6, Ldx, ZeropageY, 4),
+    (0xAE, Ldx, Absolute, 4), (0xBE, Ldx, AbsoluteY, 4),
+    (0xA0, Ldy, Immediate, 2), (0xA4, Ldy, Zeropage, 3), (0xB4, Ldy, ZeropageX, 4),
+    (0xAC, Ldy, Absolute, 4), (0xBC, Ldy, AbsoluteX, 4),
+    (0x4A, Lsr, Accumulator, 2), (0x46, Lsr, Zeropage, 5), (0x56, Lsr, ZeropageX, 6),
+    (0x4E, Lsr, Absolute, 6), (0x5E, Lsr, AbsoluteX, 7),
+    (0xEA, Nop, Implied, 2),
+    (0x09, Ora, Immediate, 2), (0x05, Ora, Zeropage, 3), (0x15, Ora, ZeropageX, 4),
+    (0x0D, Ora, Absolute, 4), (0x1D, Ora, AbsoluteX, 4), (0x19, Ora, AbsoluteY, 4),
+    (0x01, Ora, IndirectX, 6), (0x11, Ora, IndirectY, 5),
+    (0x48, Pha, Implied, 3), (0x08, Php, Implied, 3), (0x68, Pla, Implied, 4),
+    (0x28, Plp, Implied, 4),
+    (0x2A, Rol, Accumulator, 2), (0x26, Rol, Zeropage, 5), (0x36, Rol, ZeropageX, 6),
+    (0x2E, Rol, Absolute, 6), (0x3E, Rol, AbsoluteX, 7),
+    (0x6A, Ror, Accumulator, 2), (0x66, Ror, Zeropage, 5), (0x76, Ror, ZeropageX, 6),
+    (0x6E, Ror, Absolute, 6), (0x7E, Ror, AbsoluteX, 7),
+    (0x40, Rti, Implied, 6), (0x60, Rts, Implied, 6),
+    (0xE9, Sbc, Immediate, 2), (0xE5, Sbc, Zeropage, 3), (0xF5, Sbc, ZeropageX, 4),
+    (0xED, Sbc, Absolute, 4), (0xFD, Sbc, AbsoluteX, 4), (0xF9, Sbc, AbsoluteY, 4),
+    (0xE1, Sbc, IndirectX, 6), (0xF1, Sbc, IndirectY, 5),
+    (0x38, Sec, Implied, 2), (0xF8, Sed, Implied, 2), (0x78, Sei, Implied, 2),
+    (0x85, Sta, Zeropage, 3), (0x95, Sta, ZeropageX, 4), (0x8D, Sta, Absolute, 4),
+    (0x9D, Sta, AbsoluteX, 5), (0x99, Sta, AbsoluteY, 5), (0x81, Sta, IndirectX, 6),
+    (0x91, Sta, IndirectY, 6),
+    (0x86, Stx, Zeropage, 3), (0x96, Stx, ZeropageY, 4), (0x8E, Stx, Absolute, 4),
+    (0x84, Sty, Zeropage, 3), (0x94, Sty, ZeropageX, 4), (0x8C, Sty, Absolute, 4),
+    (0xAA, Tax, Implied, 2), (0xA8, Tay, Implied, 2), (0xBA, Tsx, Implied, 2),
+    (0x8A, Txa, Implied, 2), (0x9A, Txs, Implied, 2), (0x98, Tya, Implied, 2),
+];
+
+/// Memory filled with pseudo-random bytes derived from a seed, with `fixed` bytes (such
+/// as the instruction) and then writes kept on top.
+#[derive(Clone, Debug)]
+pub struct Memory {
+    seed: u32,
+    pub fixed: BTreeMap<u16, u8>,
+    pub written: BTreeMap<u16, u8>,
+}
+
+impl Memory {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            fixed: BTreeMap::new(),
+            written: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, address: u16) -> u8 {
+        let fixed = self.written.get(&address).or(self.fixed.get(&address));
+        match fixed {
+            Some(&byte) => byte,
+            None => {
+                let hash = (address as u32 ^ self.seed).wrapping_mul(0x9E37_79B1);
+                (hash >> 24) as u8
+            }
+        }
+    }
+
+    pub fn set(&mut self, address: u16, byte: u8) {
+        self.written.insert(address, byte);
+    }
+}
+
+/// A single bus access.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct Reference {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub memory: Memory,
+    pub trace: Vec<Access>,
+}
+
+impl Reference {
+    fn read(&mut self, address: u16) -> u8 {
+        let byte = self.memory.get(address);
+        self.trace.push(Access::Read(address, byte));
+        byte
+    }
+
+    fn write(&mut self, address: u16, byte: u8) {
+        self.memory.set(address, byte);
+        self.trace.push(Access::Write(address, byte));
+    }
+
+    fn read_word(&mut self, low: u16, high: u16) -> u16 {
+        let low = self.read(low);
+        let high = self.read(high);
+        u16::from_le_bytes([low, high])
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.write(0x0100 | self.s as u16, byte);
+        self.s = self.s.wrapping_sub(1);
+    }
+
+    fn pull(&mut self) -> u8 {
+        self.s = self.s.wrapping_add(1);
+        self.read(0x0100 | self.s as u16)
+    }
+
+    fn set_flag(&mut self, flag: u8, value: bool) {
+        match value {
+            true => self.p |= flag,
+            false => self.p &= !flag,
+        }
+    }
+
+    fn set_zero_negative(&mut self, value: u8) {
+        self.set_flag(ZERO, value == 0);
+        self.set_flag(NEGATIVE, value & 0x80 != 0);
+    }
+
+    /// Pulls the flags, keeping the break flag and bit 5 as they are not real flags.
+    fn pull_flags(&mut self) {
+        let pulled = self.pull();
+        self.p = (pulled & 0b1100_1111) | (self.p & 0b0011_0000);
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CARRY, register >= value);
+        self.set_zero_negative(register.wrapping_sub(value));
+    }
+
+    fn add(&mut self, value: u8) {
+        let sum = self.a as u16 + value as u16 + (self.p & CARRY) as u16;
+        let result = sum as u8;
+        self.set_flag(CARRY, sum > 0xFF);
+        self.set_flag(OVERFLOW, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.a = result;
+        self.set_zero_negative(result);
+    }
+
+    /// Runs a single instruction, returning the cycles it took or None for an
+    /// unofficial opcode.
+    pub fn step(&mut self) -> Option<u8> {
+        let opcode = self.read(self.pc);
+        let &(_, mnemonic, mode, mut cycles) =
+            OPCODES.iter().find(|(x, _, _, _)| *x == opcode)?;
+
+        // BRK reads a padding byte after it
+        let operand_size = match (mnemonic, mode) {
+            (Brk, _) => 1,
+            (_, Implied | Accumulator) => 0,
+            (_, Absolute | AbsoluteX | AbsoluteY | Indirect) => 2,
+            _ => 1,
+        };
+
+        let operand = match operand_size {
+            0 => 0,
+            1 => self.read(self.pc.wrapping_add(1)) as u16,
+            _ => self.read_word(self.pc.wrapping_add(1), self.pc.wrapping_add(2)),
+        };
+        let next = self.pc.wrapping_add(1 + operand_size);
+        self.pc = next;
+
+        // whether an indexed read crossed a page, which takes an extra cycle
+        let mut page_crossed = false;
+        let address = match mode {
+            Implied | Accumulator | Immediate | Relative => None,
+            Zeropage => Some(operand),
+            ZeropageX => Some((operand as u8).wrapping_add(self.x) as u16),
+            ZeropageY => Some((operand as u8).wrapping_add(self.y) as u16),
+            Absolute => Some(operand),
+            AbsoluteX | AbsoluteY => {
+                let index = match mode {
+                    AbsoluteX => self.x,
+                    _ => self.y,
+                };
+                let address = operand.wrapping_add(index as u16);
+                page_crossed = address & 0xFF00 != operand & 0xFF00;
+                Some(address)
+            }
+            Indirect => {
+                // the high byte does not carry into the next page
+                let high = (operand & 0xFF00) | (operand as u8).wrapping_add(1) as u16;
+                Some(self.read_word(operand, high))
+            }
+            IndirectX => {
+                let pointer = (operand as u8).wrapping_add(self.x);
+                Some(self.read_word(pointer as u16, pointer.wrapping_add(1) as u16))
+            }
+            IndirectY => {
+                let pointer = operand as u8;
+                let base = self.read_word(pointer as u16, pointer.wrapping_add(1) as u16);
+                let address = base.wrapping_add(self.y as u16);
+                page_crossed = address & 0xFF00 != base & 0xFF00;
+                Some(address)
+            }
+        };
+
+        let mut load = |cpu: &mut Self| match mode {
+            Immediate => operand as u8,
+            Accumulator => cpu.a,
+            _ => {
+                if page_crossed {
+                    cycles += 1;
+                }
+                cpu.read(address.unwrap())
+            }
+        };
+
+        match mnemonic {
+            Adc => {
+                let value = load(self);
+                self.add(value);
+            }
+            Sbc => {
+                let value = load(self);
+                self.add(!value);
+            }
+            And => {
+                self.a &= load(self);
+                self.set_zero_negative(self.a);
+            }
+            Ora => {
+                self.a |= load(self);
+                self.set_zero_negative(self.a);
+            }
+            Eor => {
+                self.a ^= load(self);
+                self.set_zero_negative(self.a);
+            }
+            Cmp => {
+                let value = load(self);
+                self.compare(self.a, value);
+            }
+            Cpx => {
+                let value = load(self);
+                self.compare(self.x, value);
+            }
+            Cpy => {
+                let value = load(self);
+                self.compare(self.y, value);
+            }
+            Bit => {
+                let value = load(self);
+                self.set_flag(ZERO, self.a & value == 0);
+                self.set_flag(OVERFLOW, value & 0x40 != 0);
+                self.set_flag(NEGATIVE, value & 0x80 != 0);
+            }
+            Lda => {
+                self.a = load(self);
+                self.set_zero_negative(self.a);
+            }
+            Ldx => {
+                self.x = load(self);
+                self.set_zero_negative(self.x);
+            }
+            Ldy => {
+                self.y = load(self);
+                self.set_zero_negative(self.y);
+            }
+            Asl | Lsr | Rol | Ror | Inc | Dec => {
+                let value = match mode {
+                    Accumulator => self.a,
+                    _ => self.read(address.unwrap()),
+                };
+                let carry = self.p & CARRY;
+                let result = match mnemonic {
+                    Asl => {
+                        self.set_flag(CARRY, value & 0x80 != 0);
+                        value << 1
+                    }
+                    Lsr => {
+                        self.set_flag(CARRY, value & 0x01 != 0);
+                        value >> 1
+                    }
+                    Rol => {
+                        self.set_flag(CARRY, value & 0x80 != 0);
+                        (value << 1) | carry
+                    }
+                    Ror => {
+                        self.set_flag(CARRY, value & 0x01 != 0);
+                        (value >> 1) | (carry << 7)
+                    }
+                    Inc => value.wrapping_add(1),
+                    _ => value.wrapping_sub(1),
+                };
+                self.set_zero_negative(result);
+                match mode {
+                    Accumulator => self.a = result,
+                    _ => self.write(address.unwrap(), result),
+                }
+            }
+            Sta => self.write(address.unwrap(), self.a),
+            Stx => self.write(address.unwrap(), self.x),
+            Sty => self.write(address.unwrap(), self.y),
+            Inx => {
+                self.x = self.x.wrapping_add(1);
+                self.set_zero_negative(self.x);
+            }
+            Iny => {
+                self.y = self.y.wrapping_add(1);
+                self.set_zero_negative(self.y);
+            }
+            Dex => {
+                self.x = self.x.wrapping_sub(1);
+                self.set_zero_negative(self.x);
+            }
+            Dey => {
+                self.y = self.y.wrapping_sub(1);
+                self.set_zero_negative(self.y);
+            }
+            Tax => {
+                self.x = self.a;
+                self.set_zero_negative(self.x);
+            }
+            Tay => {
+                self.y = self.a;
+                self.set_zero_negative(self.y);
+            }
+            Txa => {
+                self.a = self.x;
+                self.set_zero_negative(self.a);
+            }
+            Tya => {
+                self.a = self.y;
+                self.set_zero_negative(self.a);
+            }
+            Tsx => {
+                self.x = self.s;
+                self.set_zero_negative(self.x);
+            }
+            Txs => self.s = self.x,
+            Clc => self.set_flag(CARRY, false),
+            Sec => self.set_flag(CARRY, true),
+            Cli => self.set_flag(INTERRUPT_DISABLE, false),
+            Sei => self.set_flag(INTERRUPT_DISABLE, true),
+            Clv => self.set_flag(OVERFLOW, false),
+            // the decimal flag is kept, but has no effect
+            Cld => self.set_flag(0x08, false),
+            Sed => self.set_flag(0x08, true),
+            Nop => {}
+            Pha => self.push(self.a),
+            Php => self.push(self.p | BREAK),
+            Pla => {
+                self.a = self.pull();
+                self.set_zero_negative(self.a);
+            }
+            Plp => self.pull_flags(),
+            Jmp => self.pc = address.unwrap(),
+            Jsr => {
+                let [low, high] = next.wrapping_sub(1).to_le_bytes();
+                self.push(high);
+                self.push(low);
+                self.pc = operand;
+            }
+            Rts => {
+                let low = self.pull();
+                let high = self.pull();
+                self.pc = u16::from_le_bytes([low, high]).wrapping_add(1);
+            }
+            Rti => {
+                self.pull_flags();
+                let low = self.pull();
+                let high = self.pull();
+                self.pc = u16::from_le_bytes([low, high]);
+            }
+            Brk => {
+                let [low, high] = next.to_le_bytes();
+                self.push(high);
+                self.push(low);
+                self.push(self.p | BREAK);
+                self.set_flag(INTERRUPT_DISABLE, true);
+                self.pc = self.read_word(0xFFFE, 0xFFFF);
+            }
+            Bcc | Bcs | Beq | Bne | Bmi | Bpl | Bvc | Bvs => {
+                let (flag, set) = match mnemonic {
+                    Bcc => (CARRY, false),
+                    Bcs => (CARRY, true),
+                    Bne => (ZERO, false),
+                    Beq => (ZERO, true),
+                    Bpl => (NEGATIVE, false),
+                    Bmi => (NEGATIVE, true),
+                    Bvc => (OVERFLOW, false),
+                    _ => (OVERFLOW, true),
+                };
+
+                if (self.p & flag != 0) == set {
+                    let target = next.wrapping_add(operand as u8 as i8 as u16);
+                    cycles += 1;
+                    if target & 0xFF00 != next & 0xFF00 {
+                        cycles += 1;
+                    }
+                    self.pc = target;
+                }
+            }
+        }
+
+        Some(cycles)
+    }
+}